@@ -1,20 +1,126 @@
+//! Reward addresses and related contracts configured in the environment.
+
+use crate::network::Network;
 use dotenv::dotenv;
-use std::env;
+use ethers::types::{Address, U256};
+use ethers::utils::parse_ether;
+use std::collections::HashMap;
+
+/// Addresses to report on, and the network they live on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// Fee recipient of the validators.
+    pub execution_address: Address,
+    /// Withdrawal address of the validators.
+    pub consensus_address: Address,
+    /// Fee recipient splitter contracts paying out to the execution address.
+    pub splitters: Vec<Address>,
+    /// Additional addresses funding beacon deposits.
+    pub depositors: Vec<Address>,
+    /// Senders of token rewards, such as distributor contracts. Transfers of reward tokens
+    /// from other senders aren't income.
+    pub token_reward_senders: Vec<Address>,
+    /// Holders of liquid staking tokens whose daily income is reported.
+    pub lst_holders: Vec<Address>,
+    /// Rocket Pool minipools of the node and the operator's bond in each, in wei. Rocket
+    /// Pool mode is disabled if there are none.
+    pub rocket_pool_minipools: HashMap<Address, U256>,
+    /// Rocket Pool merkle distributors paying out smoothing pool and RPL rewards.
+    pub rocket_pool_distributors: Vec<Address>,
+    /// CoinGecko ids of reward tokens by contract, in addition to the built-in ones.
+    pub token_price_ids: Vec<(Address, String)>,
+    /// Network the addresses live on.
+    pub network: Network,
+}
+
+impl Config {
+    /// Reads `EXECUTION_REWARDS_ADDRESS`, `CONSENSUS_REWARDS_ADDRESS`, `SPLITTER_ADDRESSES`,
+    /// `DEPOSITOR_ADDRESSES`, `TOKEN_REWARD_SENDERS`, `LST_HOLDERS`,
+    /// `ROCKET_POOL_MINIPOOLS=<minipool>:<bond ETH>,...`, `ROCKET_POOL_DISTRIBUTORS`,
+    /// `TOKEN_PRICE_IDS=<contract>:<coin id>,...` and `NETWORK`.
+    pub fn from_env() -> Result<Self, String> {
+        dotenv().ok();
 
-pub fn execution_rewards_address() -> ethers::types::Address {
-    dotenv().ok();
+        Ok(Config {
+            execution_address: address("EXECUTION_REWARDS_ADDRESS")?,
+            consensus_address: address("CONSENSUS_REWARDS_ADDRESS")?,
+            splitters: addresses("SPLITTER_ADDRESSES")?,
+            depositors: addresses("DEPOSITOR_ADDRESSES")?,
+            token_reward_senders: addresses("TOKEN_REWARD_SENDERS")?,
+            lst_holders: addresses("LST_HOLDERS")?,
+            rocket_pool_minipools: pairs("ROCKET_POOL_MINIPOOLS", |bond| parse_ether(bond).ok())?
+                .into_iter()
+                .collect(),
+            rocket_pool_distributors: addresses("ROCKET_POOL_DISTRIBUTORS")?,
+            token_price_ids: pairs("TOKEN_PRICE_IDS", |id| {
+                Some(id.to_string()).filter(|id| !id.is_empty())
+            })?,
+            network: Network::from_env()?,
+        })
+    }
 
-    env::var("EXECUTION_REWARDS_ADDRESS")
-        .expect("EXECUTION_REWARDS_ADDRESS not found in .env")
+    /// The addresses by their role, as listed on the reports.
+    pub fn roles(&self) -> Vec<(String, String)> {
+        let mut roles = vec![
+            (
+                String::from("Execution rewards"),
+                format!("{:?}", self.execution_address),
+            ),
+            (
+                String::from("Consensus rewards"),
+                format!("{:?}", self.consensus_address),
+            ),
+        ];
+        for splitter in &self.splitters {
+            roles.push((String::from("Splitter"), format!("{:?}", splitter)));
+        }
+        for depositor in &self.depositors {
+            roles.push((String::from("Depositor"), format!("{:?}", depositor)));
+        }
+        roles
+    }
+}
+
+fn address(name: &str) -> Result<Address, String> {
+    let value = dotenv::var(name)
+        .ok()
+        .filter(|value| !value.is_empty())
+        .ok_or(format!("{} not found in .env", name))?;
+    value
+        .trim()
         .parse()
-        .unwrap()
+        .map_err(|_| format!("Invalid {} {}", name, value))
 }
 
-pub fn consensus_rewards_address() -> ethers::types::Address {
-    dotenv().ok();
+/// Comma-separated addresses, none if unset.
+fn addresses(name: &str) -> Result<Vec<Address>, String> {
+    dotenv::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(|address| address.trim())
+        .filter(|address| !address.is_empty())
+        .map(|address| {
+            address
+                .parse()
+                .map_err(|_| format!("Invalid address {} in {}", address, name))
+        })
+        .collect()
+}
 
-    env::var("CONSENSUS_REWARDS_ADDRESS")
-        .expect("CONSENSUS_REWARDS_ADDRESS not found in .env")
-        .parse()
-        .unwrap()
+/// Comma-separated `<address>:<value>` entries, none if unset.
+fn pairs<T>(name: &str, value: impl Fn(&str) -> Option<T>) -> Result<Vec<(Address, T)>, String> {
+    dotenv::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let invalid = || format!("Invalid entry {} in {}", entry, name);
+            let (address, rest) = entry.split_once(':').ok_or_else(invalid)?;
+            Ok((
+                address.trim().parse().map_err(|_| invalid())?,
+                value(rest.trim()).ok_or_else(invalid)?,
+            ))
+        })
+        .collect()
 }
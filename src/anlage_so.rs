@@ -0,0 +1,231 @@
+//! Figures for the German Anlage SO: staking income under § 22 Nr. 3 EStG and private
+//! sales of reward assets under § 23 EStG.
+
+use crate::lots::Disposal;
+use crate::reports::{Category, Report, ReportWriter};
+use crate::tax_profile::Exemption;
+use std::error::Error;
+use std::fmt::Write;
+use std::path::Path;
+
+/// The Anlage SO figures of a report, in EUR.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnlageSo {
+    pub year: i32,
+    /// Rewards received in the year, § 22 Nr. 3.
+    pub einnahmen: f64,
+    /// Gas and request fees of the year.
+    pub werbungskosten: f64,
+    /// Disposals within the holding period of acquiring the lot, § 23.
+    pub sales: Vec<Disposal>,
+    /// Disposals of units not acquired as rewards, which need their purchase added by hand.
+    pub unknown: Vec<Disposal>,
+    /// Freigrenze of income from other services, 256 €.
+    pub freigrenze_22: Exemption,
+    /// Freigrenze of gains from private sales, raised from 600 € to 1000 € in 2024.
+    pub freigrenze_23: Exemption,
+}
+
+impl AnlageSo {
+    pub fn new(report: &Report) -> Self {
+        let profile = &report.profile;
+        let fiat = |fees: bool| {
+            report
+                .entries
+                .iter()
+                .filter(|entry| (entry.category == Category::Fees) == fees)
+                .fold(0.0, |total, entry| total + entry.fiat)
+        };
+        AnlageSo {
+            year: report.year,
+            einnahmen: fiat(false),
+            werbungskosten: fiat(true),
+            sales: report
+                .disposals
+                .iter()
+                .filter(|disposal| {
+                    disposal.acquired.is_some()
+                        && !(profile.long_term_exempt && profile.long_term(disposal) == Some(true))
+                })
+                .cloned()
+                .collect(),
+            unknown: report
+                .disposals
+                .iter()
+                .filter(|disposal| disposal.acquired.is_none())
+                .cloned()
+                .collect(),
+            freigrenze_22: profile.income_exemption,
+            freigrenze_23: profile.gains_exemption(report.year),
+        }
+    }
+
+    pub fn einkuenfte(&self) -> f64 {
+        self.einnahmen - self.werbungskosten
+    }
+
+    /// Income under § 22 Nr. 3, nothing if below the Freigrenze.
+    pub fn taxable_income(&self) -> f64 {
+        self.freigrenze_22.taxable(self.einkuenfte())
+    }
+
+    pub fn gains(&self) -> f64 {
+        self.sales
+            .iter()
+            .fold(0.0, |total, disposal| total + disposal.gain())
+    }
+
+    /// Gains under § 23, nothing if below the Freigrenze.
+    pub fn taxable_gains(&self) -> f64 {
+        self.freigrenze_23.taxable(self.gains())
+    }
+}
+
+pub struct AnlageSoReport;
+
+impl ReportWriter for AnlageSoReport {
+    fn write(&self, report: &Report, directory: &Path) -> Result<(), Box<dyn Error>> {
+        if report.currency != "EUR" {
+            return Err(format!("Anlage SO is filed in EUR, not {}", report.currency).into());
+        }
+        let path = directory.join(format!("Anlage SO {}.md", report.year));
+        std::fs::write(path, render(&AnlageSo::new(report)))?;
+        Ok(())
+    }
+}
+
+fn render(so: &AnlageSo) -> String {
+    let mut markdown = format!("# Anlage SO {}\n\nAlle Beträge in EUR.\n", so.year);
+
+    writeln!(markdown, "\n## Leistungen (§ 22 Nr. 3 EStG)\n").unwrap();
+    writeln!(markdown, "| | EUR |\n| --- | ---: |").unwrap();
+    for (label, value) in [
+        ("Einnahmen", so.einnahmen),
+        ("Werbungskosten", so.werbungskosten),
+        ("Einkünfte", so.einkuenfte()),
+        ("Freigrenze", so.freigrenze_22.amount()),
+        ("**Steuerpflichtig**", so.taxable_income()),
+    ] {
+        writeln!(markdown, "| {} | {:.2} |", label, value).unwrap();
+    }
+
+    writeln!(markdown, "\n## Private Veräußerungsgeschäfte (§ 23 EStG)\n").unwrap();
+    let proceeds = so
+        .sales
+        .iter()
+        .fold(0.0, |total, sale| total + sale.proceeds);
+    let cost = so.sales.iter().fold(0.0, |total, sale| total + sale.cost);
+    writeln!(markdown, "| | EUR |\n| --- | ---: |").unwrap();
+    for (label, value) in [
+        ("Veräußerungspreis", proceeds),
+        ("Anschaffungskosten", cost),
+        ("Gewinn", so.gains()),
+        ("Freigrenze", so.freigrenze_23.amount()),
+        ("**Steuerpflichtig**", so.taxable_gains()),
+    ] {
+        writeln!(markdown, "| {} | {:.2} |", label, value).unwrap();
+    }
+
+    writeln!(
+        markdown,
+        "\nVeräußerungen innerhalb eines Jahres nach Anschaffung, nach FIFO den Rewards zugeordnet:\n"
+    )
+    .unwrap();
+    write_disposals(&mut markdown, &so.sales);
+
+    if !so.unknown.is_empty() {
+        writeln!(
+            markdown,
+            "\nVeräußerungen ohne bekannte Anschaffung, z.B. aus vor der erfassten Historie \
+             eingezahltem Principal oder Rocket Pool Bonds, sind nicht enthalten und von Hand \
+             zu prüfen:\n"
+        )
+        .unwrap();
+        write_disposals(&mut markdown, &so.unknown);
+    }
+    markdown
+}
+
+fn write_disposals(markdown: &mut String, disposals: &[Disposal]) {
+    writeln!(
+        markdown,
+        "| Veräußert | Angeschafft | Asset | Menge | Erlös | Kosten | Gewinn | ID |"
+    )
+    .unwrap();
+    writeln!(
+        markdown,
+        "| --- | --- | --- | ---: | ---: | ---: | ---: | --- |"
+    )
+    .unwrap();
+    for disposal in disposals {
+        writeln!(
+            markdown,
+            "| {} | {} | {} | {:.8} | {:.2} | {:.2} | {:.2} | {} |",
+            disposal.date.date(),
+            disposal
+                .acquired
+                .map(|acquired| acquired.date().to_string())
+                .unwrap_or_default(),
+            disposal.symbol,
+            disposal.amount,
+            disposal.proceeds,
+            disposal.cost,
+            disposal.gain(),
+            disposal.id
+        )
+        .unwrap();
+    }
+}
+
+#[test]
+fn test_anlage_so() {
+    use chrono::{NaiveDate, NaiveDateTime};
+
+    let date = |year: i32, month: u32| {
+        NaiveDate::from_ymd_opt(year, month, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    };
+    let sale = |acquired: Option<NaiveDateTime>, gain: f64| Disposal {
+        date: date(2023, 9),
+        acquired,
+        symbol: String::from("ETH"),
+        price_id: String::from("ethereum"),
+        id: String::from("0x01"),
+        amount: 0.5,
+        proceeds: 800.0 + gain,
+        cost: 800.0,
+    };
+
+    let so = AnlageSo {
+        year: 2023,
+        einnahmen: 300.0,
+        werbungskosten: 50.0,
+        sales: vec![
+            sale(Some(date(2023, 1)), 400.0),
+            sale(Some(date(2023, 2)), 250.0),
+        ],
+        unknown: vec![sale(None, 800.0)],
+        freigrenze_22: Exemption::Threshold(256.0),
+        freigrenze_23: Exemption::Threshold(600.0),
+    };
+    // Below the Freigrenze after Werbungskosten
+    assert_eq!(so.einkuenfte(), 250.0);
+    assert_eq!(so.taxable_income(), 0.0);
+    assert_eq!(so.taxable_gains(), 650.0);
+    assert_eq!(
+        AnlageSo {
+            year: 2024,
+            freigrenze_23: Exemption::Threshold(1000.0),
+            ..so.clone()
+        }
+        .taxable_gains(),
+        0.0
+    );
+
+    let markdown = render(&so);
+    assert!(markdown.contains("| **Steuerpflichtig** | 650.00 |"));
+    assert!(markdown
+        .contains("| 2023-09-01 |  | ETH | 0.50000000 | 1600.00 | 800.00 | 800.00 | 0x01 |"));
+}
@@ -0,0 +1,94 @@
+//! Validator lookups against a beacon node (`BEACON_API_URL`).
+
+use crate::http::HttpClient;
+use crate::network::Network;
+use crate::rate_limit::RateLimiter;
+use dotenv::dotenv;
+use ethers::types::U256;
+use serde::Deserialize;
+use std::env;
+use std::sync::OnceLock;
+
+#[derive(Debug, Deserialize)]
+struct ValidatorResponse {
+    data: ValidatorData,
+}
+
+#[derive(Debug, Deserialize)]
+struct ValidatorData {
+    index: String,
+    balance: String,
+    validator: ValidatorDetails,
+}
+
+#[derive(Debug, Deserialize)]
+struct ValidatorDetails {
+    withdrawal_credentials: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BeaconValidator {
+    pub index: u64,
+    /// Balance in wei of the staked asset.
+    pub balance: U256,
+    pub withdrawal_credentials: String,
+}
+
+impl BeaconValidator {
+    /// Whether the validator has EIP-7251 compounding (0x02) withdrawal credentials.
+    pub fn is_compounding(&self) -> bool {
+        self.withdrawal_credentials.starts_with("0x02")
+    }
+}
+
+fn beacon_http() -> &'static HttpClient {
+    static HTTP: OnceLock<HttpClient> = OnceLock::new();
+    HTTP.get_or_init(|| {
+        HttpClient::new(
+            "Beacon node",
+            RateLimiter::from_env("Beacon node", "BEACON_API", 20.0),
+        )
+    })
+}
+
+/// Queries a validator by index or pubkey from the beacon node at `BEACON_API_URL`.
+///
+/// `state_id` is `head` or a slot; historical slots require an archive node.
+pub async fn beacon_validator(
+    state_id: &str,
+    validator_id: &str,
+    network: Network,
+) -> Option<BeaconValidator> {
+    dotenv().ok();
+
+    let beacon_api_url = env::var("BEACON_API_URL")
+        .ok()
+        .filter(|url| !url.is_empty())?;
+    let url = format!(
+        "{}/eth/v1/beacon/states/{}/validators/{}",
+        beacon_api_url.trim_end_matches('/'),
+        state_id,
+        validator_id
+    );
+
+    let body = match beacon_http().get_text(&url, &[] as &[(&str, &str)]).await {
+        Ok(body) => body,
+        Err(error) => {
+            println!("Could not query validator {}: {}", validator_id, error);
+            return None;
+        }
+    };
+    let validator: ValidatorResponse = serde_json::from_str(&body).ok()?;
+
+    Some(BeaconValidator {
+        index: validator.data.index.parse().ok()?,
+        balance: network.consensus_to_wei(U256::from_dec_str(&validator.data.balance).ok()?),
+        withdrawal_credentials: validator.data.validator.withdrawal_credentials,
+    })
+}
+
+pub async fn validator_index(pubkey: &str, network: Network) -> Option<u64> {
+    beacon_validator("head", pubkey, network)
+        .await
+        .map(|validator| validator.index)
+}
@@ -0,0 +1,98 @@
+//! Income and fees as a Blockpit manual import CSV.
+
+use crate::reports::{Category, Entry, Report, ReportWriter};
+use std::error::Error;
+use std::path::Path;
+
+const COLUMNS: [&str; 11] = [
+    "Date (UTC)",
+    "Integration Name",
+    "Label",
+    "Outgoing Asset",
+    "Outgoing Amount",
+    "Incoming Asset",
+    "Incoming Amount",
+    "Fee Asset (optional)",
+    "Fee Amount (optional)",
+    "Comment (optional)",
+    "Trx. ID (optional)",
+];
+
+/// Integration the transactions are imported into.
+const INTEGRATION: &str = "Staking";
+
+pub struct BlockpitReport;
+
+impl ReportWriter for BlockpitReport {
+    fn write(&self, report: &Report, directory: &Path) -> Result<(), Box<dyn Error>> {
+        let path = directory.join(format!("Blockpit {}.csv", report.year));
+        // Blockpit's template is separated by semicolons
+        let mut writer = csv::WriterBuilder::new().delimiter(b';').from_path(path)?;
+        writer.write_record(COLUMNS)?;
+        for entry in &report.entries {
+            writer.write_record(record(entry))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Rewards are incoming with an income label. Fees are booked as a fee without any
+/// outgoing amount.
+fn record(entry: &Entry) -> [String; 11] {
+    let label = match entry.category {
+        Category::Execution => "Mining",
+        Category::Consensus => "Staking",
+        Category::Tokens => "Income",
+        Category::Fees => "Fee",
+    };
+    let side = |included: bool| match included {
+        true => (entry.symbol.clone(), entry.amount.clone()),
+        false => (String::new(), String::new()),
+    };
+    let fee = entry.category == Category::Fees;
+    let (incoming_asset, incoming_amount) = side(!fee);
+    let (fee_asset, fee_amount) = side(fee);
+    [
+        entry.date.format("%d.%m.%Y %H:%M:%S").to_string(),
+        INTEGRATION.to_string(),
+        label.to_string(),
+        String::new(),
+        String::new(),
+        incoming_asset,
+        incoming_amount,
+        fee_asset,
+        fee_amount,
+        format!("{} {}", entry.kind, entry.id).trim().to_string(),
+        entry.tx_hash().unwrap_or_default().to_string(),
+    ]
+}
+
+#[test]
+fn test_blockpit_record() {
+    let entry = Entry {
+        category: Category::Tokens,
+        kind: String::from("SSV"),
+        id: String::from("0x01"),
+        amount: String::from("1.5"),
+        symbol: String::from("SSV"),
+        price: 20.0,
+        fiat: 30.0,
+        ..crate::reports::sample_entry()
+    };
+
+    let record = record(&entry);
+    assert_eq!(record[..3], ["01.09.2023 12:30:00", "Staking", "Income"]);
+    assert_eq!(record[5..9], ["SSV", "1.5", "", ""]);
+    assert_eq!(record[9..], ["SSV 0x01", ""]);
+
+    let fee = Entry {
+        category: Category::Fees,
+        symbol: String::from("ETH"),
+        ..entry
+    };
+    assert_eq!(
+        self::record(&fee)[2..9],
+        ["Fee", "", "", "", "", "ETH", "1.5"]
+    );
+}
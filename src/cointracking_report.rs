@@ -0,0 +1,107 @@
+//! Income and fees as a CoinTracking CSV import.
+
+use crate::reports::{Category, Entry, Report, ReportWriter};
+use std::error::Error;
+use std::path::Path;
+
+const COLUMNS: [&str; 12] = [
+    "Type",
+    "Buy Amount",
+    "Buy Currency",
+    "Sell Amount",
+    "Sell Currency",
+    "Fee",
+    "Fee Currency",
+    "Exchange",
+    "Trade-Group",
+    "Comment",
+    "Date",
+    "Tx-ID",
+];
+
+/// Listed as the exchange of every transaction, so the import can be filtered and
+/// deleted as a whole.
+const EXCHANGE: &str = "Staking";
+
+pub struct CoinTrackingReport;
+
+impl ReportWriter for CoinTrackingReport {
+    fn write(&self, report: &Report, directory: &Path) -> Result<(), Box<dyn Error>> {
+        let path = directory.join(format!("CoinTracking {}.csv", report.year));
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(COLUMNS)?;
+        for entry in &report.entries {
+            writer.write_record(record(entry))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Rewards are bought with an income type, fees sold as `Other Fee`.
+fn record(entry: &Entry) -> [String; 12] {
+    let kind = match entry.category {
+        Category::Execution => "Mining",
+        Category::Consensus => "Staking",
+        Category::Tokens => "Reward / Bonus",
+        Category::Fees => "Other Fee",
+    };
+    let side = |included: bool| match included {
+        true => (entry.amount.clone(), entry.symbol.clone()),
+        false => (String::new(), String::new()),
+    };
+    let fee = entry.category == Category::Fees;
+    let (buy_amount, buy_currency) = side(!fee);
+    let (sell_amount, sell_currency) = side(fee);
+    [
+        kind.to_string(),
+        buy_amount,
+        buy_currency,
+        sell_amount,
+        sell_currency,
+        String::new(),
+        String::new(),
+        EXCHANGE.to_string(),
+        entry.kind.clone(),
+        entry.id.clone(),
+        entry.date.format("%d.%m.%Y %H:%M:%S").to_string(),
+        entry.tx_hash().unwrap_or_default().to_string(),
+    ]
+}
+
+#[test]
+fn test_cointracking_record() {
+    let entry = Entry {
+        category: Category::Execution,
+        kind: String::from("Block"),
+        id: String::new(),
+        ..crate::reports::sample_entry()
+    };
+
+    assert_eq!(
+        record(&entry),
+        [
+            "Mining",
+            "0.050000000000000000",
+            "ETH",
+            "",
+            "",
+            "",
+            "",
+            "Staking",
+            "Block",
+            "",
+            "01.09.2023 12:30:00",
+            "",
+        ]
+    );
+
+    let fee = Entry {
+        category: Category::Fees,
+        ..entry
+    };
+    assert_eq!(
+        record(&fee)[..5],
+        ["Other Fee", "", "", "0.050000000000000000", "ETH"]
+    );
+}
@@ -1,17 +1,25 @@
+//! Historic CoinGecko prices with a file cache per asset and currency.
+
 // https://api.coingecko.com/api/v3/coins/ethereum/history?date=30-12-2022
 
-use chrono::NaiveDateTime;
+use chrono::NaiveDate;
+use futures::stream::{self, StreamExt};
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::fs::File;
 use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 
 use dotenv::dotenv;
 use std::error::Error;
 use std::string::ToString;
-use std::sync::Arc;
+use std::sync::OnceLock;
 
-use async_throttle::RateLimiter;
+use crate::http::HttpClient;
+use crate::network::Network;
+use crate::pricing::Currency;
+use crate::rate_limit::RateLimiter;
+use ethers::types::Address;
 
 #[derive(Debug, Deserialize)]
 struct CoinGeckoResponse {
@@ -23,38 +31,143 @@ struct CoinGeckoResponse {
 
 #[derive(Debug, Deserialize)]
 struct MarketData {
-    current_price: CurrentPrice,
+    /// Prices keyed by lowercase currency code.
+    current_price: HashMap<String, f64>,
 }
 
-#[derive(Debug, Deserialize)]
-struct CurrentPrice {
-    eur: f64,
+const ETHEREUM_ID: &str = "ethereum";
+const API_URL: &str = "https://api.coingecko.com/api/v3";
+const FILE_PATH: &str = "historic_prices.json";
+/// Price queries in flight at once, beyond which waiting on the rate limiter gains nothing.
+const CONCURRENT_QUERIES: usize = 8;
+
+/// CoinGecko ids of the reward tokens we know how to price, keyed by mainnet contract address.
+const TOKEN_PRICE_IDS: [(&str, &str); 4] = [
+    ("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2", "weth"),
+    ("0xd33526068d116ce69f19a9ee46f0bd304f21a51f", "rocket-pool"),
+    ("0x9d65ff81a3c488d585bbfb0bfe3c7707c7917f54", "ssv-network"),
+    ("0xec53bf9167f50cdeb3ae105f56099aaab9061f83", "eigenlayer"),
+];
+
+/// Returns the CoinGecko id used to price the token at `contract`, from the `configured`
+/// ids or the built-in ones of mainnet, see `Config::token_price_ids`.
+pub fn token_price_id(
+    contract: &Address,
+    configured: &[(Address, String)],
+    network: Network,
+) -> Option<String> {
+    configured
+        .iter()
+        .find(|(address, _)| address == contract)
+        .map(|(_, id)| id.clone())
+        .or_else(|| {
+            TOKEN_PRICE_IDS
+                .iter()
+                .filter(|_| network == Network::Mainnet)
+                .find(|(address, _)| address.parse::<Address>().ok().as_ref() == Some(contract))
+                .map(|(_, id)| id.to_string())
+        })
 }
 
-const FILE_PATH: &str = "historic_prices.json";
+/// Price cache file, in `PRICE_CACHE_DIR` if set.
+fn price_file(coin_id: &str, currency: Currency) -> PathBuf {
+    dotenv().ok();
+
+    let name = match (coin_id, currency) {
+        (ETHEREUM_ID, Currency::Eur) => FILE_PATH.to_string(),
+        (_, Currency::Eur) => format!("historic_prices_{}.json", coin_id),
+        _ => format!(
+            "historic_prices_{}_{}.json",
+            coin_id,
+            currency.coingecko_id()
+        ),
+    };
+    match dotenv::var("PRICE_CACHE_DIR") {
+        Ok(directory) => Path::new(&directory).join(name),
+        Err(_) => PathBuf::from(name),
+    }
+}
 
-pub async fn fetch_ethereum_price(
-    datetime: &NaiveDateTime,
-    limiter: Arc<RateLimiter>,
-) -> Result<f64, Box<dyn Error>> {
-    let date = datetime.format("%d-%m-%Y").to_string();
+fn api_url() -> String {
+    dotenv::var("COINGECKO_API_URL")
+        .ok()
+        .filter(|api_url| !api_url.is_empty())
+        .unwrap_or(API_URL.to_string())
+}
+
+fn coingecko_api_key() -> Option<String> {
+    dotenv::var("COINGECKO_API_KEY")
+        .ok()
+        .filter(|api_key| !api_key.is_empty())
+}
 
-    match read_ethereum_price(&date).await {
-        Ok(val) => match val {
-            Some(val) => Ok(val),
-            None => {
-                let queried_price = query_ethereum_price_throttled(&date, limiter).await?;
-                store_ethereum_price(&date, queried_price).await?;
+/// Prices of all `(coin id, date)` pairs. Prices missing from the cache are queried
+/// concurrently, as far as the CoinGecko rate limit allows, and cached once all are known.
+pub async fn resolve_prices(
+    requests: &BTreeSet<(String, NaiveDate)>,
+    currency: Currency,
+) -> Result<HashMap<(String, NaiveDate), f64>, Box<dyn Error>> {
+    let mut prices = HashMap::new();
+    let mut missing = vec![];
 
-                Ok(queried_price)
+    for coin_id in requests
+        .iter()
+        .map(|(coin_id, _)| coin_id)
+        .collect::<BTreeSet<_>>()
+    {
+        let cached = read_prices(coin_id, currency).await?;
+        for (_, date) in requests.iter().filter(|(id, _)| id == coin_id) {
+            match cached.get(&date.format("%d-%m-%Y").to_string()) {
+                Some(price) => {
+                    prices.insert((coin_id.clone(), *date), *price);
+                }
+                None => missing.push((coin_id.clone(), *date)),
             }
-        },
-        Err(error) => Err(error),
+        }
+    }
+
+    let queried: Vec<_> = stream::iter(missing)
+        .map(|(coin_id, date)| async move {
+            let date_id = date.format("%d-%m-%Y").to_string();
+            let price = query_price(&coin_id, &date_id, currency).await;
+            ((coin_id, date), price)
+        })
+        .buffer_unordered(CONCURRENT_QUERIES)
+        .collect()
+        .await;
+
+    // Cache what was resolved before reporting a failure, so a rerun continues from there
+    let mut error = None;
+    let mut resolved: HashMap<String, HashMap<String, f64>> = HashMap::new();
+    for ((coin_id, date), price) in queried {
+        match price {
+            Ok(price) => {
+                resolved
+                    .entry(coin_id.clone())
+                    .or_default()
+                    .insert(date.format("%d-%m-%Y").to_string(), price);
+                prices.insert((coin_id, date), price);
+            }
+            Err(e) => error = Some(e),
+        }
+    }
+    for (coin_id, new_prices) in resolved {
+        store_prices(&coin_id, currency, new_prices).await?;
+    }
+
+    match error {
+        Some(error) => Err(error),
+        None => Ok(prices),
     }
 }
 
-pub async fn store_ethereum_price(date: &str, price: f64) -> std::io::Result<()> {
-    let mut historic_prices: HashMap<String, f64> = match File::open(FILE_PATH) {
+async fn store_prices(
+    coin_id: &str,
+    currency: Currency,
+    prices: HashMap<String, f64>,
+) -> std::io::Result<()> {
+    let file_path = price_file(coin_id, currency);
+    let mut historic_prices: HashMap<String, f64> = match File::open(&file_path) {
         Ok(mut file) => {
             let mut contents = String::new();
             file.read_to_string(&mut contents)?;
@@ -62,98 +175,114 @@ pub async fn store_ethereum_price(date: &str, price: f64) -> std::io::Result<()>
         }
         Err(file_error) => match file_error.kind() {
             std::io::ErrorKind::NotFound => {
-                File::create(FILE_PATH)?;
+                File::create(&file_path)?;
                 HashMap::new()
             }
-            _ => return Err(file_error.into()),
+            _ => return Err(file_error),
         },
     };
 
-    historic_prices.insert(date.to_string(), price);
+    historic_prices.extend(prices);
 
     let serialized_data = serde_json::to_string_pretty(&historic_prices)?;
 
-    File::create(FILE_PATH)?.write_all(serialized_data.as_bytes())
+    File::create(&file_path)?.write_all(serialized_data.as_bytes())
 }
 
-async fn read_ethereum_price(date: &str) -> Result<Option<f64>, Box<dyn Error>> {
+async fn read_prices(
+    coin_id: &str,
+    currency: Currency,
+) -> Result<HashMap<String, f64>, Box<dyn Error>> {
     //println!("Try to read from file...");
 
-    match File::open(FILE_PATH) {
+    let file_path = price_file(coin_id, currency);
+    match File::open(&file_path) {
         Ok(mut file) => {
             let mut contents = String::new();
             let _ = file.read_to_string(&mut contents);
-            let historic_prices: HashMap<String, f64> = serde_json::from_str(&contents)?;
-            Ok(historic_prices.get(date).cloned())
+            Ok(serde_json::from_str(&contents)?)
         }
         Err(file_error) => match file_error.kind() {
             std::io::ErrorKind::NotFound => {
-                let mut new_file = File::create(FILE_PATH)?;
+                let mut new_file = File::create(&file_path)?;
                 new_file.write_all("{}".as_bytes())?;
 
-                Ok(None)
+                Ok(HashMap::new())
             }
             _ => Err(file_error.into()),
         },
     }
 }
 
-async fn query_ethereum_price_throttled(
-    date: &str,
-    limiter: Arc<RateLimiter>,
-) -> Result<f64, Box<dyn Error>> {
-    match limiter.throttle(|| query_ethereum_price(&date)).await {
-        Ok(val) => Ok(val),
-        Err(e) => Err(e),
-    }
+/// Shared by all price queries, so consecutive failures open a single circuit.
+fn coingecko_http() -> &'static HttpClient {
+    static HTTP: OnceLock<HttpClient> = OnceLock::new();
+    HTTP.get_or_init(|| HttpClient::new("CoinGecko", coingecko_rate_limiter()))
 }
-async fn query_ethereum_price(date: &str) -> Result<f64, Box<dyn Error>> {
-    println!("Querying ETH price for date {}", date);
+
+async fn query_price(coin_id: &str, date: &str, currency: Currency) -> Result<f64, Box<dyn Error>> {
+    println!("Querying {} {} price for date {}", coin_id, currency, date);
     dotenv().ok();
 
-    let url = match dotenv::var("COINGECKO_API_KEY") {
-        Ok(coingeck_api_key) => {
-            format!(
-                "https://api.coingecko.com/api/v3/coins/ethereum/history?date={}&x_cg_api_key={}",
-                date, coingeck_api_key,
-            )
-        }
-        Err(_) => {
-            println!("No CoinGecko API Key provided. Fetching historical prices will take longer.");
-            format!(
-                "https://api.coingecko.com/api/v3/coins/ethereum/history?date={}",
-                date,
-            )
+    let mut query = vec![("date", date.to_string())];
+    match coingecko_api_key() {
+        Some(coingeck_api_key) => query.push(("x_cg_api_key", coingeck_api_key)),
+        None => {
+            println!("No CoinGecko API Key provided. Fetching historical prices will take longer.")
         }
     };
 
-    let response = reqwest::get(&url).await?;
+    let url = format!("{}/coins/{}/history", api_url(), coin_id);
+    let body = coingecko_http().get_text(&url, &query).await?;
 
-    if !response.status().is_success() {
-        return Err(response.error_for_status().err().unwrap().into());
-    }
-
-    let coin_gecko_data: CoinGeckoResponse = response.json().await?;
-    Ok(coin_gecko_data.market_data.current_price.eur)
+    let coin_gecko_data: CoinGeckoResponse = serde_json::from_str(&body)?;
+    coin_gecko_data
+        .market_data
+        .current_price
+        .get(currency.coingecko_id())
+        .copied()
+        .ok_or(format!("No {} price of {} on {}", currency, coin_id, date).into())
 }
 
-pub fn coingecko_rate_limiter() -> RateLimiter {
-    let calls_per_minute = match dotenv::var("COINGECKO_API_KEY") {
-        Ok(_) => 30,
-        Err(_) => 5,
-    };
-    println!("CoinGecko Rate-limit: {} calls / min", calls_per_minute);
+fn coingecko_rate_limiter() -> RateLimiter {
+    dotenv().ok();
 
-    let duration = std::time::Duration::from_secs(60 / calls_per_minute);
-    RateLimiter::new(duration)
+    let calls_per_minute = match coingecko_api_key() {
+        Some(_) => 30.0,
+        None => 5.0,
+    };
+    RateLimiter::from_env("CoinGecko", "COINGECKO", calls_per_minute / 60.0)
 }
 
 #[tokio::test]
 async fn test_fetch_ethereum_price_on_date() {
-    let limiter = coingecko_rate_limiter();
-    let date = NaiveDateTime::from_timestamp_opt(1650000000, 0).unwrap();
-    let price = fetch_ethereum_price(&date, Arc::new(limiter))
+    crate::fixtures::setup();
+    let date = NaiveDate::from_ymd_opt(2022, 4, 15).unwrap();
+    let request = (ETHEREUM_ID.to_string(), date);
+    let prices = resolve_prices(&BTreeSet::from([request.clone()]), Currency::Eur)
         .await
         .unwrap();
+    let price = prices[&request];
     assert_eq!(price, 2794.538482111171);
 }
+
+#[test]
+fn test_token_price_id() {
+    let rpl: Address = "0xD33526068D116cE69F19A9ee46F0bd304F21A51f"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        token_price_id(&rpl, &[], Network::Mainnet),
+        Some("rocket-pool".to_string())
+    );
+    assert_eq!(token_price_id(&rpl, &[], Network::Gnosis), None);
+    let configured = [(Address::zero(), String::from("weth"))];
+    assert_eq!(
+        token_price_id(&Address::zero(), &configured, Network::Gnosis),
+        Some("weth".to_string())
+    );
+    assert_eq!(
+        token_price_id(&Address::zero(), &[], Network::Mainnet),
+        None
+    );
+}
@@ -0,0 +1,49 @@
+//! Report as one CSV file per section, with totals as spreadsheet formulas.
+
+use crate::reports::{Cell, Report, ReportWriter, Section};
+use std::error::Error;
+use std::path::Path;
+
+pub struct CsvReport;
+
+impl ReportWriter for CsvReport {
+    fn write(&self, report: &Report, directory: &Path) -> Result<(), Box<dyn Error>> {
+        for section in &report.sections {
+            let path = directory.join(format!("{}.csv", section.title));
+            let mut writer = csv::Writer::from_path(path)?;
+            writer.write_record(&section.columns)?;
+            for row in &section.rows {
+                writer.write_record(row.iter().map(format_cell))?;
+            }
+            if !section.totals.is_empty() {
+                writer.write_record(footer(section))?;
+            }
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn format_cell(cell: &Cell) -> String {
+    match cell {
+        Cell::Date(date) => date.to_string(),
+        Cell::Integer(value) => value.to_string(),
+        Cell::Text(text) => text.clone(),
+        Cell::Amount(amount) => format!("{:.8}", amount),
+        Cell::Fiat(value) => value.to_string(),
+    }
+}
+
+/// `=SUM` formulas below the totalled columns, evaluated when opened as a spreadsheet.
+fn footer(section: &Section) -> Vec<String> {
+    let last_row = section.rows.len() + 1;
+    (0..section.columns.len())
+        .map(|column| match section.totals.contains(&column) {
+            true => {
+                let letter = (b'A' + column as u8) as char;
+                format!("=SUM({}2:{}{})", letter, letter, last_row)
+            }
+            false => String::new(),
+        })
+        .collect()
+}
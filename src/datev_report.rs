@@ -0,0 +1,286 @@
+//! Income and fees as a DATEV booking batch (EXTF Buchungsstapel) for German tax advisors.
+
+use crate::reports::{Category, Entry, Report, ReportWriter};
+use dotenv::dotenv;
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Booking text is limited to 60 characters.
+const TEXT_LENGTH: usize = 60;
+
+const COLUMNS: [&str; 24] = [
+    "Umsatz (ohne Soll/Haben-Kz)",
+    "Soll/Haben-Kennzeichen",
+    "WKZ Umsatz",
+    "Kurs",
+    "Basis-Umsatz",
+    "WKZ Basis-Umsatz",
+    "Konto",
+    "Gegenkonto (ohne BU-Schlüssel)",
+    "BU-Schlüssel",
+    "Belegdatum",
+    "Belegfeld 1",
+    "Belegfeld 2",
+    "Skonto",
+    "Buchungstext",
+    "Postensperre",
+    "Diverse Adressnummer",
+    "Geschäftspartnerbank",
+    "Sachverhalt",
+    "Zinssperre",
+    "Beleglink",
+    "Beleginfo - Art 1",
+    "Beleginfo - Inhalt 1",
+    "Beleginfo - Art 2",
+    "Beleginfo - Inhalt 2",
+];
+
+/// Standard chart of accounts the default account numbers are taken from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Chart {
+    #[default]
+    Skr03,
+    Skr04,
+}
+
+impl FromStr for Chart {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "skr03" => Ok(Chart::Skr03),
+            "skr04" => Ok(Chart::Skr04),
+            other => Err(format!("Unknown chart of accounts {}", other)),
+        }
+    }
+}
+
+/// Accounts staking income and fees are booked to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Accounts {
+    /// Holdings of the staked and reward assets.
+    pub assets: u32,
+    pub income: u32,
+    pub fees: u32,
+}
+
+impl Accounts {
+    /// Other assets, other operating income and fees of monetary transactions.
+    pub fn of(chart: Chart) -> Self {
+        match chart {
+            Chart::Skr03 => Accounts {
+                assets: 1500,
+                income: 2700,
+                fees: 4970,
+            },
+            Chart::Skr04 => Accounts {
+                assets: 1300,
+                income: 4830,
+                fees: 6855,
+            },
+        }
+    }
+
+    /// Digits of the general ledger accounts.
+    fn length(&self) -> usize {
+        [self.assets, self.income, self.fees]
+            .iter()
+            .map(|account| account.to_string().len())
+            .max()
+            .unwrap()
+    }
+}
+
+/// Writes the booking batch with the [`DatevSettings`] read from the environment.
+pub struct DatevReport;
+
+/// Consultant, client and accounts of the booking batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DatevSettings {
+    pub consultant: u32,
+    pub client: u32,
+    pub accounts: Accounts,
+}
+
+impl DatevSettings {
+    /// Reads `DATEV_CHART`, `DATEV_*_NUMBER` and `DATEV_*_ACCOUNT`, ignoring empty values.
+    pub fn from_env() -> Result<Self, String> {
+        dotenv().ok();
+
+        let var = |name: &str| dotenv::var(name).ok().filter(|value| !value.is_empty());
+        let number = |name: &str, default: u32| match var(name) {
+            Some(number) => number
+                .trim()
+                .parse::<u32>()
+                .map_err(|_| format!("Invalid {} {}", name, number)),
+            None => Ok(default),
+        };
+        let chart = match var("DATEV_CHART") {
+            Some(chart) => chart.parse()?,
+            None => Chart::default(),
+        };
+        let defaults = Accounts::of(chart);
+        Ok(DatevSettings {
+            consultant: number("DATEV_CONSULTANT_NUMBER", 1001)?,
+            client: number("DATEV_CLIENT_NUMBER", 1)?,
+            accounts: Accounts {
+                assets: number("DATEV_ASSET_ACCOUNT", defaults.assets)?,
+                income: number("DATEV_INCOME_ACCOUNT", defaults.income)?,
+                fees: number("DATEV_FEE_ACCOUNT", defaults.fees)?,
+            },
+        })
+    }
+
+    fn render(&self, report: &Report) -> String {
+        let created = chrono::Utc::now().format("%Y%m%d%H%M%S%3f");
+        let (first, last) = report.profile.fiscal_year(report.year);
+        let header = [
+            text("EXTF"),
+            String::from("700"),
+            String::from("21"),
+            text("Buchungsstapel"),
+            String::from("13"),
+            created.to_string(),
+            String::new(),
+            text(""),
+            text(""),
+            text(""),
+            self.consultant.to_string(),
+            self.client.to_string(),
+            first.format("%Y%m%d").to_string(),
+            self.accounts.length().to_string(),
+            first.format("%Y%m%d").to_string(),
+            last.format("%Y%m%d").to_string(),
+            text(&format!(
+                "Staking {}",
+                report.profile.fiscal_year_name(report.year)
+            )),
+            text(""),
+            String::from("1"),
+            String::from("0"),
+            String::from("0"),
+            text("EUR"),
+        ];
+
+        let mut lines = vec![header.join(";"), COLUMNS.join(";")];
+        lines.extend(
+            report
+                .entries
+                .iter()
+                // DATEV rejects bookings without an amount
+                .filter(|entry| format!("{:.2}", entry.fiat) != "0.00")
+                .map(|entry| self.booking(entry).join(";")),
+        );
+        lines.join("\r\n") + "\r\n"
+    }
+
+    /// Income is debited to the assets, fees are debited to the fees account.
+    fn booking(&self, entry: &Entry) -> [String; 24] {
+        let (account, contra) = match entry.category {
+            Category::Fees => (self.accounts.fees, self.accounts.assets),
+            _ => (self.accounts.assets, self.accounts.income),
+        };
+        let validator = entry.id.parse::<u64>().ok();
+        let reference = match (entry.tx_hash(), validator) {
+            (Some(hash), _) => format!("{}..{}", &hash[..10], &hash[hash.len() - 6..]),
+            (None, Some(validator)) => format!("Validator {}", validator),
+            (None, None) => entry.id.clone(),
+        };
+        let booking_text = format!("{} {} {}", entry.kind, entry.symbol, reference);
+
+        let mut booking: [String; 24] = Default::default();
+        booking[0] = format!("{:.2}", entry.fiat).replace('.', ",");
+        booking[1] = text("S");
+        booking[2] = text("EUR");
+        booking[6] = account.to_string();
+        booking[7] = contra.to_string();
+        booking[9] = entry.date.format("%d%m").to_string();
+        booking[10] = text(&entry.block.to_string());
+        booking[13] = text(
+            booking_text
+                .trim()
+                .get(..TEXT_LENGTH)
+                .unwrap_or(booking_text.trim()),
+        );
+        if let Some(hash) = entry.tx_hash() {
+            booking[20] = text("Transaktion");
+            booking[21] = text(hash);
+        }
+        if let Some(validator) = validator {
+            booking[22] = text("Validator");
+            booking[23] = text(&validator.to_string());
+        }
+        booking
+    }
+}
+
+impl ReportWriter for DatevReport {
+    fn write(&self, report: &Report, directory: &Path) -> Result<(), Box<dyn Error>> {
+        if report.currency != "EUR" {
+            return Err(format!("DATEV bookings are in EUR, not {}", report.currency).into());
+        }
+        let settings = DatevSettings::from_env()?;
+        let path = directory.join(format!("EXTF_Buchungsstapel_{}.csv", report.year));
+        std::fs::write(path, windows_1252(&settings.render(report)))?;
+        Ok(())
+    }
+}
+
+/// Quoted text field, with quotes doubled.
+fn text(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+/// DATEV reads files in the Windows code page, which matches Latin-1 for umlauts.
+fn windows_1252(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|char| u8::try_from(u32::from(char)).unwrap_or(b'?'))
+        .collect()
+}
+
+#[test]
+fn test_datev_booking() {
+    let datev = DatevSettings {
+        consultant: 1001,
+        client: 1,
+        accounts: Accounts::of(Chart::Skr04),
+    };
+    let withdrawal = crate::reports::sample_entry();
+    let hash = format!("0x{}", "ab".repeat(32));
+    let fee = Entry {
+        category: Category::Fees,
+        kind: String::from("Fee"),
+        id: hash.clone(),
+        fiat: 1.5,
+        ..withdrawal.clone()
+    };
+
+    let booking = datev.booking(&withdrawal);
+    assert_eq!(booking[..3], ["76,01", "\"S\"", "\"EUR\""]);
+    assert_eq!(booking[6..10], ["1300", "4830", "", "0109"]);
+    assert_eq!(booking[13], "\"Withdrawal ETH Validator 42\"");
+    assert_eq!(booking[22..], ["\"Validator\"", "\"42\""]);
+
+    let booking = datev.booking(&fee);
+    assert_eq!(booking[6..8], ["6855", "1300"]);
+    assert_eq!(booking[13], "\"Fee ETH 0xabababab..ababab\"");
+    assert_eq!(booking[21], format!("\"{}\"", hash));
+
+    let report = Report {
+        year: 2023,
+        currency: String::from("EUR"),
+        entries: vec![withdrawal, fee],
+        ..Default::default()
+    };
+    let lines: Vec<String> = datev
+        .render(&report)
+        .split("\r\n")
+        .map(String::from)
+        .collect();
+    assert!(lines[0].starts_with("\"EXTF\";700;21;\"Buchungsstapel\";13;"));
+    assert!(lines[0]
+        .ends_with(";1001;1;20230101;4;20230101;20231231;\"Staking 2023\";\"\";1;0;0;\"EUR\""));
+    assert_eq!(lines.len(), 5);
+    assert_eq!(windows_1252("Schlüssel"), b"Schl\xfcssel");
+}
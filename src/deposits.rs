@@ -0,0 +1,227 @@
+//! Beacon deposits, and splitting withdrawals into returned principal and rewards.
+
+use crate::beacon::validator_index;
+use crate::etherscan::transactions;
+use crate::etherscan_v2::Client;
+use crate::network::Network;
+use crate::rpc::rpc_provider;
+use crate::types::{Reward, RewardEvent};
+use ethers::abi::{decode, ParamType};
+use ethers::etherscan::account::NormalTransaction;
+use ethers::providers::Middleware;
+use ethers::types::{Address, Log, H256, U256};
+use ethers::utils::keccak256;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// Withdrawals of at least this amount (in Gwei) are full exits returning the principal.
+/// Validators are ejected before their balance drops below it.
+pub const FULL_WITHDRAWAL_THRESHOLD: u64 = 16_000_000_000;
+
+/// Whether `tx` may deposit to the beacon chain, directly or through a batch deposit
+/// contract. Only the `DepositEvent` logs of its receipt tell, as plenty of other contracts
+/// have a `deposit` function too.
+pub fn is_deposit_candidate(tx: &NormalTransaction, network: Network) -> bool {
+    tx.is_error == "0"
+        && (tx.to == Some(network.deposit_contract())
+            || (!tx.value.is_zero()
+                && tx
+                    .function_name
+                    .as_ref()
+                    .is_some_and(|name| name.to_lowercase().starts_with("deposit"))))
+}
+
+/// Decodes a `DepositEvent(bytes pubkey, bytes withdrawal_credentials, bytes amount,
+/// bytes signature, bytes index)` log into the validator pubkey and the deposited wei.
+pub fn parse_deposit_log(log: &Log, network: Network) -> Option<(String, U256)> {
+    let topic = keccak256("DepositEvent(bytes,bytes,bytes,bytes,bytes)");
+    if log.address != network.deposit_contract() || log.topics.first()?.as_bytes() != topic {
+        return None;
+    }
+
+    let tokens = decode(&vec![ParamType::Bytes; 5], &log.data).ok()?;
+    let pubkey = tokens[0].clone().into_bytes()?;
+    let amount: [u8; 8] = tokens[2].clone().into_bytes()?.try_into().ok()?;
+    let gwei = u64::from_le_bytes(amount);
+
+    Some((
+        format!("0x{}", ethers::utils::hex::encode(pubkey)),
+        network.consensus_to_wei(U256::from(gwei)),
+    ))
+}
+
+/// Splits a withdrawal into returned principal and reward, given the principal
+/// still staked with the validator.
+pub fn split_withdrawal(amount: U256, principal: U256, network: Network) -> (U256, U256) {
+    if amount < network.consensus_to_wei(U256::from(FULL_WITHDRAWAL_THRESHOLD)) {
+        return (U256::zero(), amount);
+    }
+
+    let returned = amount.min(principal);
+    (returned, amount - returned)
+}
+
+/// Splits a withdrawal of a compounding (0x02) validator into returned principal and reward.
+///
+/// Compounding validators are not skimmed, so a withdrawal of any size may contain principal.
+/// Given the validator balance after the withdrawal, everything above the principal is reward.
+/// Without it, withdrawals of at least the principal are treated as exits, and the `requested`
+/// amount of the partial withdrawal request the withdrawal results from is returned principal.
+pub fn split_compounding_withdrawal(
+    amount: U256,
+    principal: U256,
+    balance_after: Option<U256>,
+    requested: Option<U256>,
+) -> (U256, U256) {
+    let reward = match balance_after {
+        Some(balance_after) => amount.min((balance_after + amount).saturating_sub(principal)),
+        None if amount >= principal => amount - principal,
+        None => amount - amount.min(requested.unwrap_or_default()),
+    };
+
+    (amount - reward, reward)
+}
+
+/// Deposits sent by `depositors`, and the value of each of their transactions that was
+/// staked, which isn't an outgoing transfer.
+pub async fn deposit_events(
+    client: &Client,
+    depositors: &[Address],
+    network: Network,
+) -> Result<(Vec<RewardEvent>, HashMap<H256, U256>), Box<dyn Error>> {
+    // Only required if there are deposits to look up
+    let mut provider = None;
+    let mut rewards: Vec<RewardEvent> = vec![];
+    let mut staked: HashMap<H256, U256> = HashMap::new();
+
+    for depositor in depositors {
+        for tx in transactions(client, *depositor).await? {
+            if tx.from.value() != Some(depositor) || !is_deposit_candidate(&tx, network) {
+                continue;
+            }
+
+            let hash = *tx.hash.value().unwrap();
+            let receipt = match provider
+                .get_or_insert_with(|| rpc_provider(network))
+                .get_transaction_receipt(hash)
+                .await?
+            {
+                Some(receipt) => receipt,
+                None => continue,
+            };
+
+            let deposits = receipt
+                .logs
+                .iter()
+                .filter_map(|log| parse_deposit_log(log, network));
+            for (pubkey, amount) in deposits {
+                // Gnosis Chain deposits are GNO transfers, not the native value of the tx
+                if network.staking_asset() == network.native_asset() {
+                    *staked.entry(hash).or_default() += amount;
+                }
+
+                let validator_index = validator_index(&pubkey, network).await;
+                if validator_index.is_none() {
+                    println!(
+                        "Could not resolve validator index for deposit to {}",
+                        pubkey
+                    );
+                }
+
+                rewards.push(RewardEvent::Deposit {
+                    reward: Reward::new(
+                        tx.block_number.as_number().unwrap(),
+                        validator_index
+                            .map(|index| index.to_string())
+                            .unwrap_or(pubkey.clone()),
+                        tx.time_stamp.clone(),
+                        amount,
+                        network.staking_asset(),
+                        *depositor,
+                    ),
+                    pubkey,
+                });
+            }
+        }
+    }
+
+    Ok((rewards, staked))
+}
+
+#[test]
+fn test_parse_deposit_log() {
+    use ethers::abi::{encode, Token};
+    use ethers::types::H256;
+
+    let data = encode(&[
+        Token::Bytes(vec![0xab; 48]),
+        Token::Bytes(vec![0x01; 32]),
+        Token::Bytes(32_000_000_000u64.to_le_bytes().to_vec()),
+        Token::Bytes(vec![0; 96]),
+        Token::Bytes(7u64.to_le_bytes().to_vec()),
+    ]);
+    let log = Log {
+        address: Network::Mainnet.deposit_contract(),
+        topics: vec![H256::from(keccak256(
+            "DepositEvent(bytes,bytes,bytes,bytes,bytes)",
+        ))],
+        data: data.into(),
+        ..Default::default()
+    };
+
+    let (pubkey, amount) = parse_deposit_log(&log, Network::Mainnet).unwrap();
+    assert_eq!(pubkey, format!("0x{}", "ab".repeat(48)));
+    assert_eq!(amount, ethers::utils::parse_ether(32).unwrap());
+}
+
+#[test]
+fn test_split_compounding_withdrawal() {
+    let eth = |amount: &str| ethers::utils::parse_ether(amount).unwrap();
+
+    // Partial withdrawal of accrued rewards
+    assert_eq!(
+        split_compounding_withdrawal(eth("20"), eth("64"), Some(eth("64.5")), None),
+        (U256::zero(), eth("20"))
+    );
+    // Partial withdrawal reaching into the principal
+    assert_eq!(
+        split_compounding_withdrawal(eth("30"), eth("64"), Some(eth("40")), None),
+        (eth("24"), eth("6"))
+    );
+    // Exit
+    assert_eq!(
+        split_compounding_withdrawal(eth("65"), eth("64"), Some(U256::zero()), None),
+        (eth("64"), eth("1"))
+    );
+    assert_eq!(
+        split_compounding_withdrawal(eth("65"), eth("64"), None, None),
+        (eth("64"), eth("1"))
+    );
+    assert_eq!(
+        split_compounding_withdrawal(eth("20"), eth("64"), None, None),
+        (U256::zero(), eth("20"))
+    );
+    // Requested partial withdrawal reaching into the principal
+    assert_eq!(
+        split_compounding_withdrawal(eth("10"), eth("64"), None, Some(eth("10"))),
+        (eth("10"), U256::zero())
+    );
+}
+
+#[test]
+fn test_split_withdrawal() {
+    let eth = |amount: &str| ethers::utils::parse_ether(amount).unwrap();
+
+    assert_eq!(
+        split_withdrawal(eth("0.05"), eth("32"), Network::Mainnet),
+        (U256::zero(), eth("0.05"))
+    );
+    assert_eq!(
+        split_withdrawal(eth("32.01"), eth("32"), Network::Mainnet),
+        (eth("32"), eth("0.01"))
+    );
+    assert_eq!(
+        split_withdrawal(eth("31.5"), eth("32"), Network::Mainnet),
+        (eth("31.5"), U256::zero())
+    );
+}
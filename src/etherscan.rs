@@ -1,71 +1,207 @@
+//! Account data sources on Etherscan for the configured network.
+
+use crate::etherscan_v2::{Client, Error};
+use crate::network::Network;
 use dotenv::dotenv;
-use ethers::core::types::Chain;
 use ethers::etherscan::account::{
-    BeaconWithdrawalTransaction, BlockType, InternalTransaction, InternalTxQueryOption::ByAddress,
-    MinedBlock, NormalTransaction,
+    BeaconWithdrawalTransaction, ERC20TokenTransferEvent, InternalTransaction, MinedBlock,
+    NormalTransaction,
 };
-use ethers::etherscan::{errors::EtherscanError, Client};
-
+use ethers::types::{Address, BlockNumber, U64};
+use serde::de::DeserializeOwned;
 use std::env;
 
-pub fn etherscan_client() -> Result<Client, EtherscanError> {
+/// Client for `network`, using `ETHERSCAN_API_KEY` and, if set, `ETHERSCAN_API_URL`. The
+/// API key isn't needed to replay recordings.
+pub fn etherscan_client(network: Network) -> Result<Client, String> {
     dotenv().ok();
 
-    let etherscan_api_key =
-        env::var("ETHERSCAN_API_KEY").expect("ETHERSCAN_API_KEY not found in .env");
+    let etherscan_api_key = env::var("ETHERSCAN_API_KEY")
+        .ok()
+        .filter(|api_key| !api_key.is_empty());
+
+    let client = Client::new(
+        network.chain_id(),
+        etherscan_api_key.clone().unwrap_or_default(),
+    )
+    .with_env_recordings();
+    if etherscan_api_key.is_none() && !client.replays() {
+        return Err(String::from("ETHERSCAN_API_KEY not found in .env"));
+    }
+    Ok(match env::var("ETHERSCAN_API_URL") {
+        Ok(api_url) if !api_url.is_empty() => client.with_api_url(api_url),
+        _ => client,
+    })
+}
+
+/// Most records Etherscan returns for a query. Pages only reach into the first this many
+/// records, so longer histories are queried in block ranges instead.
+const MAX_RECORDS: usize = 10000;
+
+/// Mined blocks can't be queried by block range, so they are paged through.
+const MINED_BLOCKS_PAGE: usize = 1000;
+
+fn list_params(address: Address, start_block: u64) -> Vec<(&'static str, String)> {
+    vec![
+        ("address", format!("{:?}", address)),
+        ("startblock", start_block.to_string()),
+        ("endblock", "99999999".to_string()),
+        ("page", "1".to_string()),
+        ("offset", MAX_RECORDS.to_string()),
+        ("sort", "asc".to_string()),
+    ]
+}
+
+fn block_number(number: &BlockNumber) -> u64 {
+    number.as_number().map_or(0, |number| number.as_u64())
+}
+
+/// All records of a list `action` for `address`. A full result is continued by querying
+/// again from its last block, whose records may be cut off and are taken from the next
+/// query instead.
+async fn list<T: DeserializeOwned>(
+    client: &Client,
+    action: &str,
+    address: Address,
+    block: impl Fn(&T) -> u64,
+) -> Result<Vec<T>, Error> {
+    let mut records = vec![];
+    let mut start_block = 0;
+    loop {
+        let mut result: Vec<T> = client
+            .get("account", action, &list_params(address, start_block))
+            .await?;
+        if result.len() < MAX_RECORDS {
+            records.append(&mut result);
+            return Ok(records);
+        }
 
-    Client::new(Chain::Mainnet, etherscan_api_key)
+        let last_block = block(result.last().unwrap());
+        result.retain(|record| block(record) < last_block);
+        if result.is_empty() {
+            return Err(Error::Api(format!(
+                "More than {} records in block {}",
+                MAX_RECORDS, last_block
+            )));
+        }
+        records.append(&mut result);
+        start_block = last_block;
+    }
 }
 
 pub async fn internal_transactions(
     client: &Client,
-    address: ethers::types::Address,
-) -> Vec<InternalTransaction> {
+    address: Address,
+) -> Result<Vec<InternalTransaction>, Error> {
     println!("Querying internal txns for address {}", address);
 
-    client
-        .get_internal_transactions(ByAddress(address), None)
-        .await
-        .unwrap()
+    list(
+        client,
+        "txlistinternal",
+        address,
+        |tx: &InternalTransaction| block_number(&tx.block_number),
+    )
+    .await
 }
 
 pub async fn transactions(
     client: &Client,
-    address: ethers::types::Address,
-) -> Vec<NormalTransaction> {
+    address: Address,
+) -> Result<Vec<NormalTransaction>, Error> {
     println!("Querying txns for address {}", address);
 
-    client.get_transactions(&address, None).await.unwrap()
+    list(client, "txlist", address, |tx: &NormalTransaction| {
+        block_number(&tx.block_number)
+    })
+    .await
 }
 
-pub async fn produced_blocks(client: &Client, address: ethers::types::Address) -> Vec<MinedBlock> {
+pub async fn token_transfers(
+    client: &Client,
+    address: Address,
+) -> Result<Vec<ERC20TokenTransferEvent>, Error> {
+    println!("Querying token transfers for address {}", address);
+
+    list(
+        client,
+        "tokentx",
+        address,
+        |tx: &ERC20TokenTransferEvent| block_number(&tx.block_number),
+    )
+    .await
+}
+
+pub async fn produced_blocks(client: &Client, address: Address) -> Result<Vec<MinedBlock>, Error> {
     println!("Querying produced blocks for address {}", address);
 
-    client
-        .get_mined_blocks(&address, Some(BlockType::CanonicalBlocks), None)
-        .await
-        .unwrap()
+    let mut blocks = vec![];
+    for page in 1.. {
+        let mut result: Vec<MinedBlock> = client
+            .get(
+                "account",
+                "getminedblocks",
+                &[
+                    ("address", format!("{:?}", address)),
+                    ("blocktype", "blocks".to_string()),
+                    ("page", page.to_string()),
+                    ("offset", MINED_BLOCKS_PAGE.to_string()),
+                ],
+            )
+            .await?;
+        let last_page = result.len() < MINED_BLOCKS_PAGE;
+        blocks.append(&mut result);
+        if last_page {
+            break;
+        }
+    }
+    Ok(blocks)
 }
 
 pub async fn beacon_withdrawal_transactions(
     client: &Client,
-    address: ethers::types::Address,
-) -> Vec<BeaconWithdrawalTransaction> {
+    address: Address,
+) -> Result<Vec<BeaconWithdrawalTransaction>, Error> {
     println!("Querying beacon withdrawals for address {}", address);
 
-    client
-        .get_beacon_withdrawal_transactions(&address, None)
-        .await
-        .unwrap()
+    list(
+        client,
+        "txsBeaconWithdrawal",
+        address,
+        |tx: &BeaconWithdrawalTransaction| block_number(&tx.block_number),
+    )
+    .await
+}
+
+/// The last block produced at or before `timestamp`.
+pub async fn block_before(client: &Client, timestamp: u64) -> Result<U64, Error> {
+    let block: String = client
+        .get(
+            "block",
+            "getblocknobytime",
+            &[
+                ("timestamp", timestamp.to_string()),
+                ("closest", "before".to_string()),
+            ],
+        )
+        .await?;
+
+    block
+        .parse::<u64>()
+        .map(U64::from)
+        .map_err(|_| Error::Api(format!("Invalid block number {}", block)))
 }
 
 #[tokio::test]
 async fn test_beacon_withdrawal_transactions() {
+    crate::fixtures::setup();
+
     let txs = beacon_withdrawal_transactions(
-        &etherscan_client().unwrap(),
-        crate::addresses::consensus_rewards_address(),
+        &etherscan_client(Network::Mainnet).unwrap(),
+        crate::fixtures::CONSENSUS_ADDRESS.parse().unwrap(),
     )
-    .await;
+    .await
+    .unwrap();
+    assert_eq!(txs.len(), 2);
 
     for tx in txs.iter() {
         println!("Withdrawal index: {}", tx.withdrawal_index);
@@ -80,11 +216,15 @@ async fn test_beacon_withdrawal_transactions() {
 
 #[tokio::test]
 async fn test_produced_blocks() {
+    crate::fixtures::setup();
+
     let txs = produced_blocks(
-        &etherscan_client().unwrap(),
-        crate::addresses::execution_rewards_address(),
+        &etherscan_client(Network::Mainnet).unwrap(),
+        crate::fixtures::EXECUTION_ADDRESS.parse().unwrap(),
     )
-    .await;
+    .await
+    .unwrap();
+    assert_eq!(txs.len(), 1);
 
     for tx in txs.iter() {
         println!("Block reward: {}", tx.block_reward);
@@ -98,11 +238,15 @@ async fn test_produced_blocks() {
 
 #[tokio::test]
 async fn test_transactions() {
+    crate::fixtures::setup();
+
     let txs = transactions(
-        &etherscan_client().unwrap(),
-        crate::addresses::consensus_rewards_address(),
+        &etherscan_client(Network::Mainnet).unwrap(),
+        crate::fixtures::CONSENSUS_ADDRESS.parse().unwrap(),
     )
-    .await;
+    .await
+    .unwrap();
+    assert_eq!(txs.len(), 1);
 
     for tx in txs.iter() {
         println!("Transaction Hash: {:?}", tx.hash);
@@ -117,13 +261,39 @@ async fn test_transactions() {
     }
 }
 
+#[tokio::test]
+async fn test_token_transfers() {
+    crate::fixtures::setup();
+
+    let transfers = token_transfers(
+        &etherscan_client(Network::Mainnet).unwrap(),
+        crate::fixtures::EXECUTION_ADDRESS.parse().unwrap(),
+    )
+    .await
+    .unwrap();
+    assert!(transfers.is_empty());
+
+    for tx in transfers.iter() {
+        println!("Transaction Hash: {:?}", tx.hash);
+        println!("Token: {} ({:?})", tx.token_symbol, tx.contract_address);
+        println!("From: {:?}", tx.from);
+        println!("To: {:?}", tx.to);
+        println!("Value: {:?}", tx.value);
+        println!();
+    }
+}
+
 #[tokio::test]
 async fn test_internal_transactions() {
+    crate::fixtures::setup();
+
     let internal_transactions = internal_transactions(
-        &etherscan_client().unwrap(),
-        crate::addresses::execution_rewards_address(),
+        &etherscan_client(Network::Mainnet).unwrap(),
+        crate::fixtures::EXECUTION_ADDRESS.parse().unwrap(),
     )
-    .await;
+    .await
+    .unwrap();
+    assert_eq!(internal_transactions.len(), 1);
 
     for tx in internal_transactions.iter() {
         println!("Transaction Hash: {:?}", tx.hash);
@@ -137,3 +307,46 @@ async fn test_internal_transactions() {
         println!();
     }
 }
+
+#[tokio::test]
+async fn test_list_continues_full_results() {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/v2/api", listener.local_addr().unwrap());
+    let (requests, received) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        // Two records per block, the last block continuing in the next query
+        let full: Vec<usize> = (0..MAX_RECORDS).map(|index| index / 2).collect();
+        for result in [
+            serde_json::json!(full),
+            serde_json::json!([4999, 4999, 5000]),
+        ] {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let length = stream.read(&mut request).unwrap();
+            requests
+                .send(String::from_utf8_lossy(&request[..length]).to_string())
+                .unwrap();
+            let body =
+                serde_json::json!({"status": "1", "message": "OK", "result": result}).to_string();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        }
+    });
+
+    let client = Client::new(1, "key").with_api_url(url);
+    let records: Vec<u64> = list(&client, "txlist", Address::zero(), |block: &u64| *block)
+        .await
+        .unwrap();
+    assert_eq!(records.len(), MAX_RECORDS + 1);
+    assert_eq!(records[MAX_RECORDS - 3..], [4998, 4999, 4999, 5000]);
+
+    assert!(received.recv().unwrap().contains("startblock=0 "));
+    assert!(received.recv().unwrap().contains("startblock=4999 "));
+}
@@ -0,0 +1,283 @@
+//! Client for the Etherscan v2 multichain API.
+
+use crate::http::{HttpClient, HttpError};
+use crate::rate_limit::RateLimiter;
+use dotenv::dotenv;
+use ethers::utils::{hex, keccak256};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::PathBuf;
+
+const API_URL: &str = "https://api.etherscan.io/v2/api";
+const MAX_RETRIES: u32 = 5;
+
+#[derive(Debug)]
+pub enum Error {
+    Http(HttpError),
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    /// The API answered with `NOTOK` and the contained message.
+    Api(String),
+    /// Replay mode is on but no recording exists for the request.
+    MissingRecording(PathBuf),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Http(error) => write!(f, "HTTP error: {}", error),
+            Error::Io(error) => write!(f, "IO error: {}", error),
+            Error::Json(error) => write!(f, "Invalid response: {}", error),
+            Error::Api(message) => write!(f, "Etherscan error: {}", message),
+            Error::MissingRecording(path) => write!(f, "No recording at {}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<HttpError> for Error {
+    fn from(error: HttpError) -> Self {
+        Error::Http(error)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Error::Json(error)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Envelope {
+    status: String,
+    message: String,
+    result: serde_json::Value,
+}
+
+/// Client for the Etherscan v2 API, which serves all supported chains from one endpoint
+/// with a single API key.
+///
+/// Every raw response can be recorded to a directory (`ETHERSCAN_RECORDINGS`) and replayed
+/// from it without network access (`ETHERSCAN_REPLAY=true`). Transport failures are retried
+/// by `HttpClient`, rate limit responses reported with `NOTOK` are retried here.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: HttpClient,
+    api_url: String,
+    api_key: String,
+    chain_id: u64,
+    recordings: Option<PathBuf>,
+    replay: bool,
+}
+
+impl Client {
+    pub fn new(chain_id: u64, api_key: impl Into<String>) -> Self {
+        Client {
+            http: HttpClient::new(
+                "Etherscan",
+                RateLimiter::from_env("Etherscan", "ETHERSCAN", 5.0),
+            ),
+            api_url: API_URL.to_string(),
+            api_key: api_key.into(),
+            chain_id,
+            recordings: None,
+            replay: false,
+        }
+    }
+
+    /// Reads the recording options from the environment.
+    pub fn with_env_recordings(self) -> Self {
+        dotenv().ok();
+
+        match dotenv::var("ETHERSCAN_RECORDINGS") {
+            Ok(directory) if !directory.is_empty() => {
+                let replay = dotenv::var("ETHERSCAN_REPLAY").is_ok_and(|replay| replay == "true");
+                self.with_recordings(directory, replay)
+            }
+            _ => self,
+        }
+    }
+
+    pub fn with_api_url(mut self, api_url: impl Into<String>) -> Self {
+        self.api_url = api_url.into();
+        self
+    }
+
+    pub fn with_recordings(mut self, directory: impl Into<PathBuf>, replay: bool) -> Self {
+        self.recordings = Some(directory.into());
+        self.replay = replay;
+        self
+    }
+
+    /// Whether responses are replayed from recordings instead of queried.
+    pub fn replays(&self) -> bool {
+        self.replay
+    }
+
+    /// Queries `module`/`action` and deserializes the `result` of the response.
+    pub async fn get<T: DeserializeOwned>(
+        &self,
+        module: &str,
+        action: &str,
+        params: &[(&str, String)],
+    ) -> Result<T, Error> {
+        let mut query: BTreeMap<&str, String> = params.iter().cloned().collect();
+        query.insert("chainid", self.chain_id.to_string());
+        query.insert("module", module.to_string());
+        query.insert("action", action.to_string());
+
+        let recording = self.recording_path(module, action, &query);
+        if self.replay {
+            let path =
+                recording.ok_or(Error::Api("Replay requires ETHERSCAN_RECORDINGS".into()))?;
+            if !path.exists() {
+                return Err(Error::MissingRecording(path));
+            }
+            let body = std::fs::read_to_string(&path)?;
+            return parse_result(&body).map_err(|(error, _)| error);
+        }
+
+        query.insert("apikey", self.api_key.clone());
+
+        let mut attempt = 0;
+        loop {
+            let body = self.http.get_text(&self.api_url, &query).await?;
+
+            match parse_result(&body) {
+                Ok(result) => {
+                    if let Some(path) = &recording {
+                        std::fs::create_dir_all(path.parent().unwrap())?;
+                        std::fs::write(path, &body)?;
+                    }
+                    return Ok(result);
+                }
+                Err((error, true)) if attempt < MAX_RETRIES => {
+                    self.http.limiter().throttled();
+                    let delay = self.http.backoff(attempt);
+                    attempt += 1;
+                    println!(
+                        "{}, retrying in {:?} ({}/{})",
+                        error, delay, attempt, MAX_RETRIES
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err((error, _)) => return Err(error),
+            }
+        }
+    }
+
+    /// Recordings are keyed by chain, endpoint and parameters, never by API key.
+    fn recording_path(
+        &self,
+        module: &str,
+        action: &str,
+        query: &BTreeMap<&str, String>,
+    ) -> Option<PathBuf> {
+        let directory = self.recordings.as_ref()?;
+        let key = serde_json::to_string(query).unwrap();
+        let hash = hex::encode(&keccak256(key.as_bytes())[..8]);
+        Some(directory.join(format!(
+            "{}-{}-{}-{}.json",
+            self.chain_id, module, action, hash
+        )))
+    }
+}
+
+/// Parses a response body, returning whether a failure is worth retrying.
+fn parse_result<T: DeserializeOwned>(body: &str) -> Result<T, (Error, bool)> {
+    let envelope: Envelope = serde_json::from_str(body).map_err(|error| (error.into(), false))?;
+
+    if envelope.status != "1" {
+        if envelope.message.starts_with("No ") {
+            // e.g. "No transactions found", which is an empty result rather than an error
+            return serde_json::from_value(serde_json::Value::Array(vec![]))
+                .map_err(|error| (error.into(), false));
+        }
+
+        let reason = match &envelope.result {
+            serde_json::Value::String(reason) => reason.clone(),
+            _ => envelope.message.clone(),
+        };
+        let retry = reason.to_lowercase().contains("rate limit");
+        return Err((Error::Api(reason), retry));
+    }
+
+    serde_json::from_value(envelope.result).map_err(|error| (error.into(), false))
+}
+
+#[test]
+fn test_parse_result() {
+    let ok: Vec<u64> = parse_result(r#"{"status":"1","message":"OK","result":[1,2]}"#).unwrap();
+    assert_eq!(ok, vec![1, 2]);
+
+    let empty: Vec<u64> =
+        parse_result(r#"{"status":"0","message":"No transactions found","result":[]}"#).unwrap();
+    assert!(empty.is_empty());
+
+    let limited = parse_result::<Vec<u64>>(
+        r#"{"status":"0","message":"NOTOK","result":"Max rate limit reached"}"#,
+    );
+    assert!(matches!(limited, Err((Error::Api(_), true))));
+
+    let invalid =
+        parse_result::<Vec<u64>>(r#"{"status":"0","message":"NOTOK","result":"Invalid API Key"}"#);
+    assert!(matches!(invalid, Err((Error::Api(_), false))));
+}
+
+#[tokio::test]
+async fn test_rate_limit_response_throttles() {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/v2/api", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for body in [
+            r#"{"status":"0","message":"NOTOK","result":"Max rate limit reached"}"#,
+            r#"{"status":"1","message":"OK","result":[1]}"#,
+        ] {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            assert!(stream.read(&mut request).unwrap() > 0);
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        }
+    });
+
+    let client = Client::new(1, "key").with_api_url(url);
+    let rate = client.http.limiter().rate();
+    let result: Vec<u64> = client.get("account", "txlist", &[]).await.unwrap();
+    assert_eq!(result, vec![1]);
+    assert!(client.http.limiter().rate() < rate);
+}
+
+#[tokio::test]
+async fn test_replay() {
+    let directory =
+        std::env::temp_dir().join(format!("staking-tax-test-replay-{}", std::process::id()));
+    let client = Client::new(1, "key").with_recordings(&directory, true);
+    let query = BTreeMap::from([
+        ("action", "txlist".to_string()),
+        ("chainid", "1".to_string()),
+        ("module", "account".to_string()),
+    ]);
+    let path = client.recording_path("account", "txlist", &query).unwrap();
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(&path, r#"{"status":"1","message":"OK","result":[7]}"#).unwrap();
+
+    let result: Vec<u64> = client.get("account", "txlist", &[]).await.unwrap();
+    assert_eq!(result, vec![7]);
+}
@@ -0,0 +1,186 @@
+//! EIP-7002 withdrawal and EIP-7251 consolidation requests sent from execution layer.
+
+use crate::network::Network;
+use ethers::etherscan::account::{BeaconWithdrawalTransaction, NormalTransaction};
+use ethers::types::{Address, H256, U256, U64};
+
+/// EIP-7002 system contract for execution layer triggered exits and partial withdrawals.
+pub const WITHDRAWAL_REQUEST_CONTRACT: &str = "0x00000961ef480eb55e80d19ad83579a64c007002";
+/// EIP-7251 system contract for consolidation requests.
+pub const CONSOLIDATION_REQUEST_CONTRACT: &str = "0x0000bbddc7ce488642fb579f8b00f3a590007251";
+
+const PUBKEY_LENGTH: usize = 48;
+
+/// A request to withdraw `amount` wei from the validator with `pubkey`, or to exit it if zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WithdrawalRequest {
+    pub block: U64,
+    pub timestamp: String,
+    pub hash: H256,
+    /// Address sending the request and paying its fee.
+    pub from: Address,
+    pub pubkey: String,
+    pub amount: U256,
+    /// Request fee paid to the system contract.
+    pub fee: U256,
+}
+
+/// A consolidation moving the balance of the `source` validator to the `target` validator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsolidationRequest {
+    pub block: U64,
+    pub timestamp: String,
+    pub hash: H256,
+    /// Address sending the request and paying its fee.
+    pub from: Address,
+    pub source: String,
+    pub target: String,
+    /// Request fee paid to the system contract.
+    pub fee: U256,
+}
+
+pub fn withdrawal_request_contract() -> Address {
+    WITHDRAWAL_REQUEST_CONTRACT.parse().unwrap()
+}
+
+pub fn consolidation_request_contract() -> Address {
+    CONSOLIDATION_REQUEST_CONTRACT.parse().unwrap()
+}
+
+fn pubkey(bytes: &[u8]) -> String {
+    format!("0x{}", ethers::utils::hex::encode(bytes))
+}
+
+/// Decodes the `pubkey ++ amount` calldata of a withdrawal request, the amount being
+/// big-endian Gwei.
+pub fn parse_withdrawal_request(input: &[u8]) -> Option<(String, u64)> {
+    if input.len() != PUBKEY_LENGTH + 8 {
+        return None;
+    }
+
+    let gwei = u64::from_be_bytes(input[PUBKEY_LENGTH..].try_into().ok()?);
+    Some((pubkey(&input[..PUBKEY_LENGTH]), gwei))
+}
+
+/// Decodes the `source pubkey ++ target pubkey` calldata of a consolidation request.
+pub fn parse_consolidation_request(input: &[u8]) -> Option<(String, String)> {
+    if input.len() != 2 * PUBKEY_LENGTH {
+        return None;
+    }
+
+    Some((
+        pubkey(&input[..PUBKEY_LENGTH]),
+        pubkey(&input[PUBKEY_LENGTH..]),
+    ))
+}
+
+/// Withdrawal requests sent with `txs`, in chronological order.
+pub fn withdrawal_requests(txs: &[NormalTransaction], network: Network) -> Vec<WithdrawalRequest> {
+    let mut requests: Vec<WithdrawalRequest> = txs
+        .iter()
+        .filter(|tx| tx.is_error == "0" && tx.to == Some(withdrawal_request_contract()))
+        .filter_map(|tx| {
+            let (pubkey, amount) = parse_withdrawal_request(&tx.input)?;
+            Some(WithdrawalRequest {
+                block: tx.block_number.as_number()?,
+                timestamp: tx.time_stamp.clone(),
+                hash: *tx.hash.value()?,
+                from: *tx.from.value()?,
+                pubkey,
+                amount: network.consensus_to_wei(U256::from(amount)),
+                fee: tx.value,
+            })
+        })
+        .collect();
+
+    requests.sort_by_key(|request| request.block);
+    requests
+}
+
+/// The first withdrawal of `validator_index` after a request sent in `block`.
+pub fn resulting_withdrawal(
+    block: U64,
+    validator_index: u64,
+    withdrawals: &[BeaconWithdrawalTransaction],
+) -> Option<&BeaconWithdrawalTransaction> {
+    withdrawals
+        .iter()
+        .filter(|withdrawal| withdrawal.validator_index == validator_index)
+        .filter(|withdrawal| withdrawal.block_number.as_number().unwrap() > block)
+        .min_by_key(|withdrawal| withdrawal.withdrawal_index)
+}
+
+/// Consolidation requests sent with `txs`, in chronological order.
+pub fn consolidation_requests(txs: &[NormalTransaction]) -> Vec<ConsolidationRequest> {
+    let mut requests: Vec<ConsolidationRequest> = txs
+        .iter()
+        .filter(|tx| tx.is_error == "0" && tx.to == Some(consolidation_request_contract()))
+        .filter_map(|tx| {
+            let (source, target) = parse_consolidation_request(&tx.input)?;
+            Some(ConsolidationRequest {
+                block: tx.block_number.as_number()?,
+                timestamp: tx.time_stamp.clone(),
+                hash: *tx.hash.value()?,
+                from: *tx.from.value()?,
+                source,
+                target,
+                fee: tx.value,
+            })
+        })
+        .collect();
+
+    requests.sort_by_key(|request| request.block);
+    requests
+}
+
+#[test]
+fn test_parse_consolidation_request() {
+    let mut input = vec![0x11; PUBKEY_LENGTH];
+    input.extend(vec![0x22; PUBKEY_LENGTH]);
+
+    assert_eq!(
+        parse_consolidation_request(&input),
+        Some((
+            format!("0x{}", "11".repeat(PUBKEY_LENGTH)),
+            format!("0x{}", "22".repeat(PUBKEY_LENGTH))
+        ))
+    );
+    assert_eq!(parse_consolidation_request(&input[1..]), None);
+}
+
+#[test]
+fn test_parse_withdrawal_request() {
+    let mut input = vec![0x11; PUBKEY_LENGTH];
+    input.extend(1_000_000_000u64.to_be_bytes());
+
+    assert_eq!(
+        parse_withdrawal_request(&input),
+        Some((format!("0x{}", "11".repeat(PUBKEY_LENGTH)), 1_000_000_000))
+    );
+    assert_eq!(parse_withdrawal_request(&input[..PUBKEY_LENGTH]), None);
+}
+
+#[test]
+fn test_resulting_withdrawal() {
+    let withdrawal = |index: u64, validator: u64, block: u64| -> BeaconWithdrawalTransaction {
+        serde_json::from_value(serde_json::json!({
+            "blockNumber": block.to_string(),
+            "timestamp": "1700000000",
+            "withdrawalIndex": index.to_string(),
+            "validatorIndex": validator.to_string(),
+            "address": format!("{:?}", Address::zero()),
+            "amount": "1000000000",
+        }))
+        .unwrap()
+    };
+    let withdrawals = vec![
+        withdrawal(1, 7, 100),
+        withdrawal(2, 8, 150),
+        withdrawal(3, 7, 200),
+        withdrawal(4, 7, 300),
+    ];
+
+    let linked = resulting_withdrawal(U64::from(120), 7, &withdrawals).unwrap();
+    assert_eq!(linked.withdrawal_index, 3);
+    assert!(resulting_withdrawal(U64::from(300), 7, &withdrawals).is_none());
+}
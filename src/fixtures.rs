@@ -0,0 +1,150 @@
+//! Local HTTP stand-in for Etherscan and CoinGecko, serving the responses in `fixtures/`.
+//!
+//! Requests are matched against `fixtures/routes.json`: a route applies if the path is
+//! equal and all of its query parameters are present with the given values.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+
+pub const EXECUTION_ADDRESS: &str = "0x1111111111111111111111111111111111111111";
+pub const CONSENSUS_ADDRESS: &str = "0x2222222222222222222222222222222222222222";
+
+/// Configuration the pipeline reads besides the fixture server and addresses, cleared so a
+/// developer's `.env` can't reach live services or change the output.
+const CLEARED: [&str; 36] = [
+    "INFURA_API_KEY",
+    "COINGECKO_API_KEY",
+    "BEACON_API_URL",
+    "NETWORK",
+    "SPLITTER_ADDRESSES",
+    "DEPOSITOR_ADDRESSES",
+    "TOKEN_PRICE_IDS",
+    "TOKEN_REWARD_SENDERS",
+    "ROCKET_POOL_MINIPOOLS",
+    "ROCKET_POOL_DISTRIBUTORS",
+    "LST_HOLDERS",
+    "ETHERSCAN_RECORDINGS",
+    "ETHERSCAN_REPLAY",
+    "ETHERSCAN_CALLS_PER_MONTH",
+    "COINGECKO_CALLS_PER_MONTH",
+    "RPC_CALLS_PER_SECOND",
+    "RPC_CALLS_PER_MONTH",
+    "BEACON_API_CALLS_PER_SECOND",
+    "BEACON_API_CALLS_PER_MONTH",
+    "CURRENCY",
+    "PRICE_POLICY",
+    "DATEV_CHART",
+    "DATEV_CONSULTANT_NUMBER",
+    "DATEV_CLIENT_NUMBER",
+    "DATEV_ASSET_ACCOUNT",
+    "DATEV_INCOME_ACCOUNT",
+    "DATEV_FEE_ACCOUNT",
+    "TAX_PROFILE",
+    "TAX_FISCAL_YEAR_START",
+    "TAX_UTC_OFFSET",
+    "TAX_INCOME_RECOGNITION",
+    "TAX_COST_BASIS",
+    "TAX_HOLDING_PERIOD_MONTHS",
+    "TAX_INCOME_EXEMPTION",
+    "TAX_GAINS_EXEMPTION",
+    "TAX_LONG_TERM_EXEMPT",
+];
+
+#[derive(Debug, Deserialize)]
+struct Route {
+    path: String,
+    #[serde(default)]
+    query: HashMap<String, String>,
+    file: String,
+}
+
+pub fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures")
+}
+
+/// Starts the fixture server once per test binary and points the configuration at it.
+/// Every test uses the same server, as the environment is shared between threads.
+pub fn setup() -> &'static str {
+    static URL: OnceLock<String> = OnceLock::new();
+
+    URL.get_or_init(|| {
+        let routes: Vec<Route> = serde_json::from_str(
+            &std::fs::read_to_string(fixtures_dir().join("routes.json")).unwrap(),
+        )
+        .unwrap();
+        let routes = Arc::new(routes);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let routes = routes.clone();
+                thread::spawn(move || serve(stream.unwrap(), &routes));
+            }
+        });
+
+        let cache =
+            std::env::temp_dir().join(format!("staking-tax-fixtures-{}", std::process::id()));
+        std::fs::create_dir_all(&cache).unwrap();
+
+        std::env::set_var("ETHERSCAN_API_KEY", "fixtures");
+        std::env::set_var("ETHERSCAN_API_URL", format!("{}/v2/api", url));
+        std::env::set_var("ETHERSCAN_CALLS_PER_SECOND", "100");
+        std::env::set_var("COINGECKO_API_URL", format!("{}/api/v3", url));
+        std::env::set_var("COINGECKO_CALLS_PER_SECOND", "100");
+        std::env::set_var("PRICE_CACHE_DIR", cache);
+        std::env::set_var("EXECUTION_REWARDS_ADDRESS", EXECUTION_ADDRESS);
+        std::env::set_var("CONSENSUS_REWARDS_ADDRESS", CONSENSUS_ADDRESS);
+        std::env::set_var("RPC_URL", format!("{}/rpc", url));
+        // Set empty rather than removed, so `.env` doesn't fill them in
+        for name in CLEARED {
+            std::env::set_var(name, "");
+        }
+
+        url
+    })
+}
+
+fn serve(mut stream: TcpStream, routes: &[Route]) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+    // Skip the headers, fixture requests have no body
+    let mut header = String::new();
+    while reader.read_line(&mut header).unwrap() > 2 {
+        header.clear();
+    }
+
+    let target = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let url = reqwest::Url::parse(&format!("http://localhost{}", target)).unwrap();
+    let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+
+    let route = routes.iter().find(|route| {
+        route.path == url.path()
+            && route
+                .query
+                .iter()
+                .all(|(key, value)| query.get(key) == Some(value))
+    });
+    let (status, body) = match route {
+        Some(route) => (
+            "200 OK",
+            std::fs::read_to_string(fixtures_dir().join(&route.file)).unwrap(),
+        ),
+        None => ("404 Not Found", format!("No fixture for {}", target)),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+    .unwrap();
+}
@@ -0,0 +1,271 @@
+//! Annual report as a self-contained, printable HTML document for the accountant.
+
+use crate::reports::{Cell, Report, ReportWriter, Section};
+use std::error::Error;
+use std::fmt::Write;
+use std::path::Path;
+
+const STYLE: &str = "
+body { font-family: Helvetica, Arial, sans-serif; font-size: 10pt; margin: 2em; }
+h1 { font-size: 22pt; }
+h2 { font-size: 14pt; margin-top: 2em; }
+table { border-collapse: collapse; margin: 0.5em 0; }
+th, td { border-bottom: 1px solid #ccc; padding: 2px 8px; text-align: left; }
+td.number, th.number { text-align: right; }
+tr.total td { font-weight: bold; border-top: 2px solid #000; }
+code { font-size: 8pt; }
+.cover { page-break-after: always; }
+section { page-break-inside: avoid; }
+@media print { body { margin: 0; } }
+";
+
+const CHART_WIDTH: f64 = 800.0;
+const CHART_HEIGHT: f64 = 240.0;
+const CHART_PADDING: f64 = 40.0;
+
+pub struct HtmlReport;
+
+impl ReportWriter for HtmlReport {
+    fn write(&self, report: &Report, directory: &Path) -> Result<(), Box<dyn Error>> {
+        let path = directory.join(format!("Annual Report {}.html", report.year));
+        std::fs::write(path, render(report))?;
+        Ok(())
+    }
+}
+
+fn render(report: &Report) -> String {
+    let title = format!("Staking Rewards {}", report.year);
+    let mut html = String::new();
+    writeln!(html, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>").unwrap();
+    writeln!(html, "<meta charset=\"utf-8\">\n<title>{}</title>", title).unwrap();
+    writeln!(html, "<style>{}</style>\n</head>\n<body>", STYLE).unwrap();
+
+    writeln!(html, "<section class=\"cover\">\n<h1>{}</h1>", title).unwrap();
+    writeln!(
+        html,
+        "<p>Annual report of staking rewards, valued in {}.</p>",
+        escape(&report.currency)
+    )
+    .unwrap();
+    writeln!(html, "<h2>Addresses</h2>\n<table>").unwrap();
+    for (role, address) in &report.addresses {
+        writeln!(
+            html,
+            "<tr><td>{}</td><td><code>{}</code></td></tr>",
+            escape(role),
+            escape(address)
+        )
+        .unwrap();
+    }
+    writeln!(html, "</table>\n<h2>Methodology</h2>").unwrap();
+    for paragraph in &report.methodology {
+        writeln!(html, "<p>{}</p>", escape(paragraph)).unwrap();
+    }
+    writeln!(html, "</section>").unwrap();
+
+    writeln!(html, "<section>\n<h2>Summary</h2>\n<table>").unwrap();
+    writeln!(
+        html,
+        "<tr><th>Report</th><th class=\"number\">{}</th></tr>",
+        escape(&report.currency)
+    )
+    .unwrap();
+    for (section, total) in report.summary() {
+        writeln!(
+            html,
+            "<tr><td>{}</td><td class=\"number\">{:.2}</td></tr>",
+            escape(&section.title),
+            total
+        )
+        .unwrap();
+    }
+    writeln!(
+        html,
+        "<tr class=\"total\"><td>Net</td><td class=\"number\">{:.2}</td></tr>",
+        report.net()
+    )
+    .unwrap();
+    writeln!(html, "</table>\n</section>").unwrap();
+
+    writeln!(
+        html,
+        "<section>\n<h2>Balance ({})</h2>\n{}</section>",
+        escape(&report.balance_symbol),
+        balance_chart(report)
+    )
+    .unwrap();
+
+    for section in &report.sections {
+        write_section(&mut html, section);
+    }
+
+    writeln!(html, "</body>\n</html>").unwrap();
+    html
+}
+
+fn write_section(html: &mut String, section: &Section) {
+    // Numeric columns are right aligned, judged by the first row
+    let numeric: Vec<bool> = (0..section.columns.len())
+        .map(|column| {
+            section
+                .rows
+                .first()
+                .and_then(|row| row.get(column))
+                .is_some_and(|cell| cell.value().is_some())
+        })
+        .collect();
+    let class = |column: usize| match numeric[column] {
+        true => " class=\"number\"",
+        false => "",
+    };
+
+    writeln!(html, "<h2>{}</h2>\n<table>\n<tr>", escape(&section.title)).unwrap();
+    for (column, title) in section.columns.iter().enumerate() {
+        writeln!(html, "<th{}>{}</th>", class(column), escape(title)).unwrap();
+    }
+    writeln!(html, "</tr>").unwrap();
+    for row in &section.rows {
+        write!(html, "<tr>").unwrap();
+        for (column, cell) in row.iter().enumerate() {
+            let text = escape(&cell.to_string());
+            match cell {
+                // Transaction hashes and pubkeys
+                Cell::Text(id) if id.starts_with("0x") => {
+                    write!(html, "<td><code>{}</code></td>", text).unwrap()
+                }
+                _ => write!(html, "<td{}>{}</td>", class(column), text).unwrap(),
+            }
+        }
+        writeln!(html, "</tr>").unwrap();
+    }
+    if !section.totals.is_empty() {
+        write!(html, "<tr class=\"total\">").unwrap();
+        for column in 0..section.columns.len() {
+            let total = match (column, section.totals.contains(&column)) {
+                (0, _) => String::from("Total"),
+                (_, true) if section.amount_total() == Some(column) => {
+                    Cell::Amount(section.total(column)).to_string()
+                }
+                (_, true) => Cell::Fiat(section.total(column)).to_string(),
+                (_, false) => String::new(),
+            };
+            write!(html, "<td{}>{}</td>", class(column), total).unwrap();
+        }
+        writeln!(html, "</tr>").unwrap();
+    }
+    writeln!(html, "</table>").unwrap();
+}
+
+/// Inline SVG of the balance over the year, with a tick per month.
+fn balance_chart(report: &Report) -> String {
+    let steps = report.balance_steps();
+    let max = steps
+        .iter()
+        .map(|(_, balance)| *balance)
+        .fold(0.0, f64::max);
+    let scale = if max > 0.0 { max } else { 1.0 };
+    let x = |fraction: f64| CHART_PADDING + fraction * (CHART_WIDTH - 2.0 * CHART_PADDING);
+    let y = |balance: f64| {
+        CHART_HEIGHT - CHART_PADDING - balance / scale * (CHART_HEIGHT - 2.0 * CHART_PADDING)
+    };
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-size=\"10\">\n",
+        CHART_WIDTH, CHART_HEIGHT
+    );
+    writeln!(
+        svg,
+        "<polyline fill=\"none\" stroke=\"#000\" points=\"{},{} {},{} {},{}\"/>",
+        x(0.0),
+        y(scale),
+        x(0.0),
+        y(0.0),
+        x(1.0),
+        y(0.0)
+    )
+    .unwrap();
+    for month in 0..12 {
+        let label = report.month(month).name();
+        writeln!(
+            svg,
+            "<text x=\"{:.1}\" y=\"{}\">{}</text>",
+            x(month as f64 / 12.0),
+            CHART_HEIGHT - CHART_PADDING + 14.0,
+            &label[..3]
+        )
+        .unwrap();
+    }
+    writeln!(
+        svg,
+        "<text x=\"0\" y=\"{}\">{:.2}</text>\n<text x=\"0\" y=\"{}\">0</text>",
+        y(scale) + 4.0,
+        max,
+        y(0.0) + 4.0
+    )
+    .unwrap();
+
+    let points: Vec<String> = steps
+        .iter()
+        .map(|(fraction, balance)| format!("{:.1},{:.1}", x(*fraction), y(*balance)))
+        .collect();
+    writeln!(
+        svg,
+        "<polyline fill=\"none\" stroke=\"#1f6feb\" stroke-width=\"2\" points=\"{}\"/>",
+        points.join(" ")
+    )
+    .unwrap();
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[test]
+fn test_html_report() {
+    use crate::reports::Role;
+    use crate::tax_profile::TaxProfile;
+    use chrono::NaiveDate;
+
+    let date = |month: u32| {
+        NaiveDate::from_ymd_opt(2023, month, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    };
+    let report = Report {
+        year: 2023,
+        currency: String::from("EUR"),
+        profile: TaxProfile::default(),
+        sections: vec![Section {
+            title: String::from("Fees 2023"),
+            role: Role::Expense,
+            columns: ["ID", "ETH", "EUR"].map(String::from).to_vec(),
+            rows: vec![vec![
+                Cell::Text(String::from("0x01")),
+                Cell::Amount(0.01),
+                Cell::Fiat(16.0),
+            ]],
+            totals: vec![1, 2],
+        }],
+        entries: vec![],
+        movements: vec![],
+        disposals: vec![],
+        addresses: vec![(String::from("Execution rewards"), String::from("0x11"))],
+        methodology: vec![String::from("Fees < rewards & income")],
+        opening_balance: 1.0,
+        balance: vec![(date(7), 2.0)],
+        balance_symbol: String::from("ETH"),
+    };
+
+    let html = render(&report);
+    assert!(html.contains("<p>Fees &lt; rewards &amp; income</p>"));
+    assert!(html.contains("<td><code>0x01</code></td>"));
+    assert!(html.contains("<tr class=\"total\"><td>Net</td><td class=\"number\">-16.00</td></tr>"));
+    // Opening balance at half height until 1 am German time on 1 July, then the maximum
+    assert!(html.contains("40.0,120.0 397.1,120.0 397.1,40.0 760.0,40.0"));
+}
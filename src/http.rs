@@ -0,0 +1,286 @@
+//! HTTP client shared by all data sources, with retries and a circuit breaker.
+
+use crate::rate_limit::RateLimiter;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::Serialize;
+use std::fmt;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub enum HttpError {
+    Request(reqwest::Error),
+    Status(StatusCode, String),
+    /// Too many consecutive failures, requests to the provider are suspended.
+    CircuitOpen(&'static str),
+    /// The monthly call budget of the provider is used up.
+    BudgetExhausted(String),
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::Request(error) => write!(f, "Request failed: {}", error),
+            HttpError::Status(status, body) => write!(f, "HTTP {}: {}", status, body),
+            HttpError::CircuitOpen(name) => write!(f, "{} is unavailable, circuit open", name),
+            HttpError::BudgetExhausted(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+impl From<reqwest::Error> for HttpError {
+    fn from(error: reqwest::Error) -> Self {
+        HttpError::Request(error)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 5,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(120),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff for the `attempt`th retry (starting at 0), scaled by `jitter`
+    /// in `[0.5, 1.0]` so concurrent clients don't retry in lockstep.
+    pub fn backoff(&self, attempt: u32, jitter: f64) -> Duration {
+        let exponential = self.base_delay.saturating_mul(2u32.saturating_pow(attempt));
+        exponential.min(self.max_delay).mul_f64(jitter)
+    }
+}
+
+/// Parses a `Retry-After` header given either in seconds or as an HTTP date.
+pub fn retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    match value.parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => {
+            let date = DateTime::parse_from_rfc2822(value).ok()?;
+            (date.with_timezone(&Utc) - now).to_std().ok()
+        }
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Suspends requests for `cooldown` after `threshold` consecutive failures, then lets
+/// a single request through to probe whether the provider recovered.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    failures: AtomicU32,
+    open_until: Mutex<Option<Instant>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            threshold,
+            cooldown,
+            failures: AtomicU32::new(0),
+            open_until: Mutex::new(None),
+        }
+    }
+
+    pub fn allows_request(&self) -> bool {
+        let mut open_until = self.open_until.lock().unwrap();
+        match *open_until {
+            Some(until) if Instant::now() < until => false,
+            Some(_) => {
+                // Half open: allow a probe, a further failure reopens immediately
+                *open_until = None;
+                self.failures.store(self.threshold - 1, Ordering::SeqCst);
+                true
+            }
+            None => true,
+        }
+    }
+
+    pub fn record_success(&self) {
+        self.failures.store(0, Ordering::SeqCst);
+    }
+
+    pub fn record_failure(&self) {
+        let failures = self.failures.fetch_add(1, Ordering::SeqCst) + 1;
+        if failures >= self.threshold {
+            *self.open_until.lock().unwrap() = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+/// HTTP client shared by the price and chain data modules, retrying throttled and failed
+/// requests with exponential backoff and jitter, honoring `Retry-After`. Each attempt
+/// takes a token from the provider's `RateLimiter`, clones share it.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    name: &'static str,
+    client: reqwest::Client,
+    policy: RetryPolicy,
+    breaker: Arc<CircuitBreaker>,
+    limiter: Arc<RateLimiter>,
+}
+
+impl HttpClient {
+    pub fn new(name: &'static str, limiter: RateLimiter) -> Self {
+        HttpClient {
+            name,
+            client: reqwest::Client::new(),
+            policy: RetryPolicy::default(),
+            breaker: Arc::new(CircuitBreaker::new(5, Duration::from_secs(300))),
+            limiter: Arc::new(limiter),
+        }
+    }
+
+    /// Rate limiter of the provider, for callers detecting throttling in the response body.
+    pub fn limiter(&self) -> &RateLimiter {
+        &self.limiter
+    }
+
+    /// Jittered backoff before the `attempt`th retry, also used by callers retrying on
+    /// errors reported in the response body.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let jitter = rand::thread_rng().gen_range(0.5..=1.0);
+        self.policy.backoff(attempt, jitter)
+    }
+
+    pub async fn get_text<Q: Serialize + ?Sized>(
+        &self,
+        url: &str,
+        query: &Q,
+    ) -> Result<String, HttpError> {
+        self.send(|| self.client.get(url).query(query)).await
+    }
+
+    pub async fn post_json<B: Serialize + ?Sized>(
+        &self,
+        url: &str,
+        body: &B,
+    ) -> Result<String, HttpError> {
+        self.send(|| self.client.post(url).json(body)).await
+    }
+
+    /// Sends the request built by `request`, building it anew for each attempt.
+    async fn send(
+        &self,
+        request: impl Fn() -> reqwest::RequestBuilder,
+    ) -> Result<String, HttpError> {
+        if !self.breaker.allows_request() {
+            return Err(HttpError::CircuitOpen(self.name));
+        }
+
+        let mut attempt = 0;
+        loop {
+            self.limiter
+                .acquire()
+                .await
+                .map_err(HttpError::BudgetExhausted)?;
+
+            let (error, delay) = match request().send().await {
+                Ok(response) if response.status().is_success() => {
+                    self.breaker.record_success();
+                    self.limiter.succeeded();
+                    return Ok(response.text().await?);
+                }
+                Ok(response) if is_retryable(response.status()) => {
+                    if response.status() == StatusCode::TOO_MANY_REQUESTS {
+                        self.limiter.throttled();
+                    }
+                    let delay = retry_after(response.headers(), Utc::now());
+                    let status = response.status();
+                    (HttpError::Status(status, response.text().await?), delay)
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    return Err(HttpError::Status(status, response.text().await?));
+                }
+                Err(error) if error.is_timeout() || error.is_connect() => {
+                    (HttpError::Request(error), None)
+                }
+                Err(error) => return Err(error.into()),
+            };
+
+            if attempt >= self.policy.max_retries {
+                self.breaker.record_failure();
+                return Err(error);
+            }
+
+            let delay = delay.unwrap_or_else(|| self.backoff(attempt));
+            println!("{}: {}, retrying in {:?}", self.name, error, delay);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[test]
+fn test_backoff() {
+    let policy = RetryPolicy {
+        max_retries: 5,
+        base_delay: Duration::from_secs(1),
+        max_delay: Duration::from_secs(10),
+    };
+
+    assert_eq!(policy.backoff(0, 1.0), Duration::from_secs(1));
+    assert_eq!(policy.backoff(3, 1.0), Duration::from_secs(8));
+    assert_eq!(policy.backoff(4, 1.0), Duration::from_secs(10));
+    assert_eq!(policy.backoff(3, 0.5), Duration::from_secs(4));
+}
+
+#[test]
+fn test_retry_after() {
+    let now = DateTime::parse_from_rfc2822("Wed, 21 Oct 2015 07:28:00 GMT")
+        .unwrap()
+        .with_timezone(&Utc);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(RETRY_AFTER, "120".parse().unwrap());
+    assert_eq!(retry_after(&headers, now), Some(Duration::from_secs(120)));
+
+    headers.insert(
+        RETRY_AFTER,
+        "Wed, 21 Oct 2015 07:29:00 GMT".parse().unwrap(),
+    );
+    assert_eq!(retry_after(&headers, now), Some(Duration::from_secs(60)));
+
+    assert_eq!(retry_after(&HeaderMap::new(), now), None);
+}
+
+#[test]
+fn test_circuit_breaker() {
+    let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+
+    breaker.record_failure();
+    assert!(breaker.allows_request());
+    breaker.record_failure();
+    assert!(!breaker.allows_request());
+
+    let breaker = CircuitBreaker::new(2, Duration::ZERO);
+    breaker.record_failure();
+    breaker.record_failure();
+    // Cooldown elapsed, a probe is allowed and a single failure reopens the circuit
+    assert!(breaker.allows_request());
+    breaker.record_failure();
+    *breaker.open_until.lock().unwrap() = Some(Instant::now() + Duration::from_secs(60));
+    assert!(!breaker.allows_request());
+}
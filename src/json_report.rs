@@ -0,0 +1,109 @@
+//! Report as a single JSON document, for further processing by other tools.
+
+use crate::reports::{Cell, Report, ReportWriter};
+use serde_json::{json, Map, Value};
+use std::error::Error;
+use std::path::Path;
+
+pub struct JsonReport;
+
+impl ReportWriter for JsonReport {
+    fn write(&self, report: &Report, directory: &Path) -> Result<(), Box<dyn Error>> {
+        let path = directory.join(format!("Staking Rewards {}.json", report.year));
+        std::fs::write(path, serde_json::to_string_pretty(&to_json(report))?)?;
+        Ok(())
+    }
+}
+
+/// Rows become objects keyed by column, totals an object of the totalled columns.
+fn to_json(report: &Report) -> Value {
+    let sections: Vec<Value> = report
+        .sections
+        .iter()
+        .map(|section| {
+            let rows: Vec<Value> = section
+                .rows
+                .iter()
+                .map(|row| {
+                    let row: Map<String, Value> = section
+                        .columns
+                        .iter()
+                        .cloned()
+                        .zip(row.iter().map(cell_value))
+                        .collect();
+                    Value::Object(row)
+                })
+                .collect();
+            let totals: Map<String, Value> = section
+                .totals
+                .iter()
+                .map(|column| {
+                    (
+                        section.columns[*column].clone(),
+                        json!(section.total(*column)),
+                    )
+                })
+                .collect();
+            json!({
+                "title": section.title,
+                "rows": rows,
+                "totals": totals,
+            })
+        })
+        .collect();
+
+    json!({
+        "year": report.year,
+        "currency": report.currency,
+        "sections": sections,
+        "net": report.net(),
+    })
+}
+
+fn cell_value(cell: &Cell) -> Value {
+    match cell {
+        Cell::Date(date) => json!(date.to_string()),
+        Cell::Integer(value) => json!(value),
+        Cell::Text(text) => json!(text),
+        Cell::Amount(value) | Cell::Fiat(value) => json!(value),
+    }
+}
+
+#[test]
+fn test_json_report() {
+    use crate::reports::{Role, Section};
+    use chrono::NaiveDate;
+
+    let date = NaiveDate::from_ymd_opt(2023, 9, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    let section = |title: &str, role: Role, amount: f64, fiat: f64| Section {
+        title: title.to_string(),
+        role,
+        columns: ["Date", "ETH", "EUR"].map(String::from).to_vec(),
+        rows: vec![
+            vec![Cell::Date(date), Cell::Amount(amount), Cell::Fiat(fiat)],
+            vec![Cell::Date(date), Cell::Amount(amount), Cell::Fiat(fiat)],
+        ],
+        totals: vec![1, 2],
+    };
+    let report = Report {
+        year: 2023,
+        currency: String::from("EUR"),
+        sections: vec![
+            section("Rewards", Role::Income, 0.5, 800.0),
+            section("Fees", Role::Expense, 0.01, 16.0),
+        ],
+        ..Default::default()
+    };
+
+    let json = to_json(&report);
+    assert_eq!(
+        json["sections"][0]["rows"][1]["Date"],
+        "2023-09-01 00:00:00"
+    );
+    assert_eq!(json["sections"][0]["totals"]["ETH"], 1.0);
+    assert_eq!(json["sections"][1]["totals"]["EUR"], 32.0);
+    assert_eq!(json["net"], 1568.0);
+}
@@ -0,0 +1,95 @@
+//! Income and fees as a Koinly universal import CSV.
+
+use crate::reports::{Category, Entry, Report, ReportWriter};
+use std::error::Error;
+use std::path::Path;
+
+const COLUMNS: [&str; 12] = [
+    "Date",
+    "Sent Amount",
+    "Sent Currency",
+    "Received Amount",
+    "Received Currency",
+    "Fee Amount",
+    "Fee Currency",
+    "Net Worth Amount",
+    "Net Worth Currency",
+    "Label",
+    "Description",
+    "TxHash",
+];
+
+pub struct KoinlyReport;
+
+impl ReportWriter for KoinlyReport {
+    fn write(&self, report: &Report, directory: &Path) -> Result<(), Box<dyn Error>> {
+        let path = directory.join(format!("Koinly {}.csv", report.year));
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(COLUMNS)?;
+        for entry in &report.entries {
+            writer.write_record(record(entry, &report.currency))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Rewards are received with an income label, fees sent with the `cost` label.
+fn record(entry: &Entry, currency: &str) -> [String; 12] {
+    let (sent, received, label) = match entry.category {
+        Category::Execution => (false, true, "mining"),
+        Category::Consensus => (false, true, "staking"),
+        Category::Tokens => (false, true, "reward"),
+        Category::Fees => (true, false, "cost"),
+    };
+    let side = |included: bool| match included {
+        true => (entry.amount.clone(), entry.symbol.clone()),
+        false => (String::new(), String::new()),
+    };
+    let (sent_amount, sent_currency) = side(sent);
+    let (received_amount, received_currency) = side(received);
+    [
+        entry.date.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        sent_amount,
+        sent_currency,
+        received_amount,
+        received_currency,
+        String::new(),
+        String::new(),
+        format!("{:.2}", entry.fiat),
+        currency.to_string(),
+        label.to_string(),
+        format!("{} {}", entry.kind, entry.id).trim().to_string(),
+        entry.tx_hash().unwrap_or_default().to_string(),
+    ]
+}
+
+#[test]
+fn test_koinly_record() {
+    let hash = format!("0x{}", "ab".repeat(32));
+    let entry = Entry {
+        category: Category::Fees,
+        kind: String::from("Fee"),
+        id: hash.clone(),
+        amount: String::from("0.001000000000000000"),
+        price: 1500.0,
+        fiat: 1.5,
+        ..crate::reports::sample_entry()
+    };
+
+    let record = record(&entry, "EUR");
+    assert_eq!(record[0], "2023-09-01 12:30:00 UTC");
+    assert_eq!(record[1..5], ["0.001000000000000000", "ETH", "", ""]);
+    assert_eq!(record[7..10], ["1.50", "EUR", "cost"]);
+    assert_eq!(record[11], hash);
+
+    let withdrawal = Entry {
+        category: Category::Consensus,
+        kind: String::from("Withdrawal"),
+        id: String::from("42"),
+        ..entry
+    };
+    let record = self::record(&withdrawal, "EUR");
+    assert_eq!(record[1..5], ["", "", "0.001000000000000000", "ETH"]);
+    assert_eq!(record[9..12], ["staking", "Withdrawal 42", ""]);
+}
@@ -0,0 +1,197 @@
+//! Income and fees as a double-entry ledger for Beancount and hledger.
+//!
+//! Rewards are booked to `Assets:<symbol>:<address>` as lots at their cost in the
+//! reporting currency against `Income:Staking:*`, fees are paid from it at their price
+//! to `Expenses:Gas`. The other posting of each transaction is left for the tool to
+//! balance.
+
+use crate::reports::{Category, Entry, Report, ReportWriter};
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt::Write;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dialect {
+    Beancount,
+    Hledger,
+}
+
+pub struct BeancountReport;
+
+impl ReportWriter for BeancountReport {
+    fn write(&self, report: &Report, directory: &Path) -> Result<(), Box<dyn Error>> {
+        let path = directory.join(format!("Staking Rewards {}.beancount", report.year));
+        std::fs::write(path, render(report, Dialect::Beancount))?;
+        Ok(())
+    }
+}
+
+pub struct HledgerReport;
+
+impl ReportWriter for HledgerReport {
+    fn write(&self, report: &Report, directory: &Path) -> Result<(), Box<dyn Error>> {
+        let path = directory.join(format!("Staking Rewards {}.journal", report.year));
+        std::fs::write(path, render(report, Dialect::Hledger))?;
+        Ok(())
+    }
+}
+
+fn render(report: &Report, dialect: Dialect) -> String {
+    let mut ledger = String::new();
+    let mut accounts = BTreeSet::new();
+    for entry in &report.entries {
+        accounts.insert(asset_account(entry));
+        accounts.insert(counter_account(entry).to_string());
+    }
+
+    match dialect {
+        Dialect::Beancount => {
+            writeln!(
+                ledger,
+                "option \"title\" \"Staking Rewards {}\"",
+                report.year
+            )
+            .unwrap();
+            writeln!(
+                ledger,
+                "option \"operating_currency\" \"{}\"",
+                report.currency
+            )
+            .unwrap();
+            writeln!(ledger).unwrap();
+            let (opened, _) = report.profile.fiscal_year(report.year);
+            for account in &accounts {
+                // Fees are paid at their price without reducing a lot, which would need
+                // the lots of earlier years
+                let booking = match account.starts_with("Assets:") {
+                    true => " \"NONE\"",
+                    false => "",
+                };
+                writeln!(ledger, "{} open {}{}", opened, account, booking).unwrap();
+            }
+        }
+        Dialect::Hledger => {
+            for account in &accounts {
+                writeln!(ledger, "account {}", account).unwrap();
+            }
+        }
+    }
+
+    for entry in &report.entries {
+        writeln!(ledger).unwrap();
+        write_transaction(&mut ledger, entry, &report.currency, dialect);
+    }
+    ledger
+}
+
+fn write_transaction(ledger: &mut String, entry: &Entry, currency: &str, dialect: Dialect) {
+    let date = entry.date.date();
+    let narration = format!("{} {}", entry.kind, entry.id).trim().to_string();
+    match (dialect, entry.tx_hash()) {
+        (Dialect::Beancount, hash) => {
+            writeln!(ledger, "{} * \"{}\"", date, narration).unwrap();
+            if let Some(hash) = hash {
+                writeln!(ledger, "  tx_hash: \"{}\"", hash).unwrap();
+            }
+        }
+        (Dialect::Hledger, Some(hash)) => {
+            writeln!(ledger, "{} * {}  ; tx_hash: {}", date, narration, hash).unwrap()
+        }
+        (Dialect::Hledger, None) => writeln!(ledger, "{} * {}", date, narration).unwrap(),
+    }
+
+    let units = format!("{} {}", entry.amount, commodity(&entry.symbol));
+    let price = format!("{} {}", entry.price, currency);
+    let asset = asset_account(entry);
+    let counter = counter_account(entry);
+    match (entry.category, dialect) {
+        (Category::Fees, _) => {
+            writeln!(ledger, "  {}  -{} @ {}", asset, units, price).unwrap();
+        }
+        // hledger balances by the price and has no lots
+        (_, Dialect::Hledger) => {
+            writeln!(ledger, "  {}  {} @ {}", asset, units, price).unwrap();
+        }
+        (_, Dialect::Beancount) => {
+            writeln!(ledger, "  {}  {} {{{}}} @ {}", asset, units, price, price).unwrap();
+        }
+    }
+    writeln!(ledger, "  {}", counter).unwrap();
+}
+
+fn asset_account(entry: &Entry) -> String {
+    format!("Assets:{}:{}", commodity(&entry.symbol), entry.address)
+}
+
+fn counter_account(entry: &Entry) -> &'static str {
+    match entry.category {
+        Category::Execution => "Income:Staking:Execution",
+        Category::Consensus => "Income:Staking:Consensus",
+        Category::Tokens => "Income:Staking:Tokens",
+        Category::Fees => "Expenses:Gas",
+    }
+}
+
+/// Beancount only allows upper case commodities, e.g. `XDAI` for xDAI.
+fn commodity(symbol: &str) -> String {
+    symbol
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect::<String>()
+        .to_uppercase()
+}
+
+#[test]
+fn test_ledger_report() {
+    let withdrawal = crate::reports::sample_entry();
+    let hash = format!("0x{}", "ab".repeat(32));
+    let fee = Entry {
+        category: Category::Fees,
+        kind: String::from("Fee"),
+        id: hash.clone(),
+        amount: String::from("0.001000000000000000"),
+        price: 1500.0,
+        fiat: 1.5,
+        ..withdrawal.clone()
+    };
+    let report = Report {
+        year: 2023,
+        currency: String::from("EUR"),
+        entries: vec![withdrawal, fee],
+        ..Default::default()
+    };
+
+    assert_eq!(
+        render(&report, Dialect::Beancount),
+        format!(
+            "option \"title\" \"Staking Rewards 2023\"
+option \"operating_currency\" \"EUR\"
+
+2023-01-01 open Assets:ETH:0x11 \"NONE\"
+2023-01-01 open Expenses:Gas
+2023-01-01 open Income:Staking:Consensus
+
+2023-09-01 * \"Withdrawal 42\"
+  Assets:ETH:0x11  0.050000000000000000 ETH {{1520.12 EUR}} @ 1520.12 EUR
+  Income:Staking:Consensus
+
+2023-09-01 * \"Fee {hash}\"
+  tx_hash: \"{hash}\"
+  Assets:ETH:0x11  -0.001000000000000000 ETH @ 1500 EUR
+  Expenses:Gas
+"
+        )
+    );
+
+    let journal = render(&report, Dialect::Hledger);
+    assert!(journal.starts_with("account Assets:ETH:0x11\n"));
+    assert!(journal.contains("  Assets:ETH:0x11  0.050000000000000000 ETH @ 1520.12 EUR\n"));
+    assert!(journal.contains(&format!("2023-09-01 * Fee {}  ; tx_hash: {}\n", hash, hash)));
+}
+
+#[test]
+fn test_commodity() {
+    assert_eq!(commodity("xDAI"), "XDAI");
+    assert_eq!(commodity("rETH"), "RETH");
+}
@@ -0,0 +1,51 @@
+//! Staking reward accounting for Ethereum validators.
+//!
+//! The pipeline runs in three stages, each usable on its own:
+//!
+//! 1. [`processing::process_transactions`] fetches chain data for the configured
+//!    [`addresses::Config`] from Etherscan, the beacon chain and an RPC node, and
+//!    classifies it into unpriced [`types::RewardEvent`]s.
+//! 2. [`pricing::Pricer`] values the events in a fiat currency, caching historic prices.
+//! 3. [`reports::write_reports`] lays out the yearly [`reports::Report`] and writes it
+//!    in each selected [`reports::ReportFormat`], while [`processing`] provides the
+//!    aggregations (balance, earnings, principal per validator).
+//!
+//! Configuration is read from the environment or a `.env` file, see `.env.example`.
+
+#![warn(missing_docs)]
+
+pub mod addresses;
+pub(crate) mod anlage_so;
+pub(crate) mod beacon;
+pub(crate) mod blockpit_report;
+pub(crate) mod cointracking_report;
+pub(crate) mod conversion;
+pub(crate) mod csv_report;
+pub(crate) mod datev_report;
+pub(crate) mod deposits;
+pub(crate) mod etherscan;
+pub(crate) mod etherscan_v2;
+pub(crate) mod execution_requests;
+#[cfg(test)]
+mod fixtures;
+pub(crate) mod html_report;
+pub(crate) mod http;
+pub(crate) mod json_report;
+pub(crate) mod koinly_report;
+pub(crate) mod ledger_report;
+pub(crate) mod liquid_staking;
+pub mod lots;
+pub(crate) mod markdown_report;
+pub mod network;
+pub(crate) mod pdf_report;
+pub mod pricing;
+pub mod processing;
+pub(crate) mod rate_limit;
+pub mod reports;
+pub(crate) mod rocket_pool;
+pub(crate) mod rpc;
+pub mod tax_profile;
+pub mod types;
+pub(crate) mod uk_report;
+pub(crate) mod us_report;
+pub(crate) mod xlsx_report;
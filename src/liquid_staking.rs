@@ -0,0 +1,235 @@
+//! Income of liquid staking tokens, which accrues without transfers.
+
+use crate::etherscan::{block_before, token_transfers};
+use crate::etherscan_v2::Client;
+use crate::network::Network;
+use crate::rpc::rpc_provider;
+use crate::types::{Reward, RewardEvent};
+use chrono::{Duration, NaiveDateTime, Utc};
+use ethers::contract::abigen;
+use ethers::etherscan::account::ERC20TokenTransferEvent;
+use ethers::types::{Address, U256};
+use ethers::utils::WEI_IN_ETHER;
+use std::error::Error;
+use std::sync::Arc;
+
+abigen!(
+    LiquidStakingTokenContract,
+    r#"[
+        function balanceOf(address account) external view returns (uint256)
+        function getExchangeRate() external view returns (uint256)
+        function exchangeRate() external view returns (uint256)
+    ]"#
+);
+
+/// How a liquid staking token passes its yield on to holders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Accrual {
+    /// Balances grow daily (stETH).
+    Rebasing,
+    /// Balances stay constant while the ETH value per token grows (rETH, cbETH).
+    ExchangeRate,
+}
+
+impl Accrual {
+    /// Accrual of the liquid staking token `symbol`, rebasing if it isn't known.
+    pub fn of(symbol: &str) -> Self {
+        LIQUID_STAKING_TOKENS
+            .iter()
+            .find(|token| token.symbol == symbol)
+            .map_or(Accrual::Rebasing, |token| token.accrual)
+    }
+
+    /// Kind of income the accrual is listed as on the reports.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Accrual::Rebasing => "Rebase",
+            Accrual::ExchangeRate => "Exchange Rate Gain",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LiquidStakingToken {
+    pub symbol: &'static str,
+    pub contract: &'static str,
+    pub accrual: Accrual,
+}
+
+pub const LIQUID_STAKING_TOKENS: [LiquidStakingToken; 3] = [
+    LiquidStakingToken {
+        symbol: "stETH",
+        contract: "0xae7ab96520de3a18e5e111b5eaab095312d7fe84",
+        accrual: Accrual::Rebasing,
+    },
+    LiquidStakingToken {
+        symbol: "rETH",
+        contract: "0xae78736cd615f374d3085123a210448e74fc6393",
+        accrual: Accrual::ExchangeRate,
+    },
+    LiquidStakingToken {
+        symbol: "cbETH",
+        contract: "0xbe9895146f7af43049ca1c1ae358b0541ea49704",
+        accrual: Accrual::ExchangeRate,
+    },
+];
+
+/// Rebase income of a day: balance growth not explained by transfers.
+pub fn rebase_income(previous: U256, current: U256, inflow: U256, outflow: U256) -> U256 {
+    (current + outflow).saturating_sub(previous + inflow)
+}
+
+/// Exchange-rate income of a day in wei: ETH value growth of the tokens held at the start of it.
+pub fn exchange_rate_income(balance: U256, previous_rate: U256, current_rate: U256) -> U256 {
+    balance * current_rate.saturating_sub(previous_rate) / WEI_IN_ETHER
+}
+
+/// Sums the tokens received and sent by `holder` between `start` and `end` (inclusive).
+fn transfer_flows(
+    transfers: &[&ERC20TokenTransferEvent],
+    holder: Address,
+    start: i64,
+    end: i64,
+) -> (U256, U256) {
+    let mut inflow = U256::zero();
+    let mut outflow = U256::zero();
+
+    for tx in transfers {
+        let timestamp = tx.time_stamp.parse::<i64>().unwrap();
+        if timestamp < start || timestamp > end {
+            continue;
+        }
+        if tx.to == Some(holder) {
+            inflow += tx.value;
+        }
+        if tx.from == holder {
+            outflow += tx.value;
+        }
+    }
+
+    (inflow, outflow)
+}
+
+pub async fn liquid_staking_events(
+    client: &Client,
+    holder: Address,
+    network: Network,
+) -> Result<Vec<RewardEvent>, Box<dyn Error>> {
+    if network != Network::Mainnet {
+        println!("Liquid staking tokens are only supported on mainnet");
+        return Ok(vec![]);
+    }
+
+    let provider = Arc::new(rpc_provider(network));
+    let transfers = token_transfers(client, holder).await?;
+    let yesterday = Utc::now().date_naive() - Duration::days(1);
+
+    let mut rewards: Vec<RewardEvent> = vec![];
+
+    for token in LIQUID_STAKING_TOKENS {
+        let address: Address = token.contract.parse().unwrap();
+        let token_transfers: Vec<_> = transfers
+            .iter()
+            .filter(|tx| tx.contract_address == address)
+            .collect();
+
+        let first_transfer = match token_transfers.first() {
+            Some(tx) => tx,
+            None => continue,
+        };
+
+        println!("Computing {} income for address {}", token.symbol, holder);
+
+        let contract = LiquidStakingTokenContract::new(address, provider.clone());
+        let mut day =
+            NaiveDateTime::from_timestamp_opt(first_transfer.time_stamp.parse().unwrap(), 0)
+                .unwrap()
+                .date();
+        let mut previous: Option<(U256, U256)> = None;
+
+        while day <= yesterday {
+            let start = day.and_hms_opt(0, 0, 0).unwrap().timestamp();
+            let end = start + 86400 - 1;
+            let block = block_before(client, end as u64).await?;
+
+            let balance = contract.balance_of(holder).block(block).call().await?;
+            let rate = match token.accrual {
+                Accrual::Rebasing => WEI_IN_ETHER,
+                Accrual::ExchangeRate if token.symbol == "rETH" => {
+                    contract.get_exchange_rate().block(block).call().await?
+                }
+                Accrual::ExchangeRate => contract.exchange_rate().block(block).call().await?,
+            };
+
+            // Nothing was held before the first transfer, so a rebase on its day is income,
+            // while tokens valued by exchange rate only earn from the following day on
+            let (previous_balance, previous_rate) = previous.unwrap_or((U256::zero(), rate));
+            let income = match token.accrual {
+                Accrual::Rebasing => {
+                    let (inflow, outflow) = transfer_flows(&token_transfers, holder, start, end);
+                    rebase_income(previous_balance, balance, inflow, outflow)
+                }
+                Accrual::ExchangeRate => {
+                    exchange_rate_income(previous_balance, previous_rate, rate)
+                }
+            };
+
+            if !income.is_zero() {
+                rewards.push(RewardEvent::LiquidStakingReward {
+                    reward: Reward::new(
+                        block,
+                        format!("{} {:?}", token.symbol, holder),
+                        end.to_string(),
+                        income,
+                        network.native_asset(),
+                        holder,
+                    ),
+                    symbol: token.symbol.to_string(),
+                });
+            }
+
+            previous = Some((balance, rate));
+            day += Duration::days(1);
+        }
+    }
+
+    Ok(rewards)
+}
+
+#[test]
+fn test_rebase_income() {
+    let eth = WEI_IN_ETHER;
+    assert_eq!(
+        rebase_income(eth * 10, eth * 11, eth, U256::zero()),
+        U256::zero()
+    );
+    assert_eq!(
+        rebase_income(eth * 10, eth * 6 + 1000, U256::zero(), eth * 4),
+        U256::from(1000)
+    );
+    // Negative rebases are not reported as income
+    assert_eq!(
+        rebase_income(eth * 10, eth * 9, U256::zero(), U256::zero()),
+        U256::zero()
+    );
+}
+
+#[test]
+fn test_exchange_rate_income() {
+    let eth = WEI_IN_ETHER;
+    assert_eq!(
+        exchange_rate_income(
+            eth * 2,
+            U256::from(1_100_000_000_000_000_000u64),
+            U256::from(1_100_100_000_000_000_000u64)
+        ),
+        U256::from(200_000_000_000_000u64)
+    );
+}
+
+#[test]
+fn test_accrual_label() {
+    assert_eq!(Accrual::of("stETH").label(), "Rebase");
+    assert_eq!(Accrual::of("rETH").label(), "Exchange Rate Gain");
+    assert_eq!(Accrual::of("cbETH").label(), "Exchange Rate Gain");
+}
@@ -0,0 +1,542 @@
+//! Lots of the reward assets, matching disposals to the rewards acquired first.
+//!
+//! Lots are opened by income at its value when received, and by refunds. Deposits stake the
+//! oldest lots held, which aren't disposed of, and principal returned from validators opens
+//! these lots again. The part of a deposit funded from elsewhere is acquired when deposited,
+//! at its value then. Principal deposited before the tracked history and returned Rocket
+//! Pool bonds were bought outside of the tracked addresses, so their lots have no known
+//! acquisition and are the oldest. Outgoing transfers, their gas and request fees dispose of
+//! the oldest lots of the asset, at their own cost or the average cost of all lots held,
+//! depending on the [`CostBasis`].
+
+use crate::network::Network;
+use crate::tax_profile::CostBasis;
+use crate::types::{Reward, RewardEvent};
+use chrono::NaiveDateTime;
+use ethers::types::U256;
+use ethers::utils::format_units;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// Amounts below this are rounding left over from splitting lots.
+pub const DUST: f64 = 1e-12;
+
+/// Units of an asset acquired together.
+#[derive(Debug, Clone, PartialEq)]
+struct Lot {
+    /// `None` if bought outside of the tracked addresses at an unknown cost.
+    acquired: Option<NaiveDateTime>,
+    amount: f64,
+    /// Cost per unit in the reporting currency.
+    price: f64,
+    /// Whether the units were received as income.
+    income: bool,
+    /// Whether staked units were held by the addresses before, rather than funded from
+    /// elsewhere.
+    redeposited: bool,
+}
+
+impl Lot {
+    fn of(movement: &Movement) -> Self {
+        Lot {
+            acquired: movement.acquired,
+            amount: movement.amount,
+            price: movement.price,
+            income: movement.income,
+            redeposited: false,
+        }
+    }
+}
+
+/// Part of an outgoing transfer matched to a single lot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Disposal {
+    /// When the units were disposed of.
+    pub date: NaiveDateTime,
+    /// When the lot was acquired, if the units could be matched to one.
+    pub acquired: Option<NaiveDateTime>,
+    /// Symbol of the asset.
+    pub symbol: String,
+    /// CoinGecko id of the asset, keeping its lots apart.
+    pub price_id: String,
+    /// Transaction or validator the disposal belongs to.
+    pub id: String,
+    /// Units disposed of.
+    pub amount: f64,
+    /// Value of the disposed units in the reporting currency.
+    pub proceeds: f64,
+    /// Cost of the disposed units in the reporting currency.
+    pub cost: f64,
+}
+
+impl Disposal {
+    /// Proceeds less cost, a loss if negative.
+    pub fn gain(&self) -> f64 {
+        self.proceeds - self.cost
+    }
+}
+
+/// A change of the holdings of a reward asset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movement {
+    /// When the holdings changed.
+    pub date: NaiveDateTime,
+    /// Symbol of the asset.
+    pub symbol: String,
+    /// CoinGecko id of the asset, keeping its lots apart.
+    pub price_id: String,
+    /// Transaction or validator the movement belongs to.
+    pub id: String,
+    /// Units acquired if positive, disposed of if negative.
+    pub amount: f64,
+    /// Value per unit in the reporting currency, the cost of acquired units.
+    pub price: f64,
+    /// When acquired units were bought, the deposit for returned principal. `None` if
+    /// unknown, in which case `price` is zero.
+    pub acquired: Option<NaiveDateTime>,
+    /// Whether acquired units were received as income, also if returned after being staked.
+    pub income: bool,
+    /// Whether units held by the addresses were staked with a validator, or are returned
+    /// from it. Neither is a disposal or an acquisition.
+    pub staked: bool,
+}
+
+/// Acquisitions and disposals of the reward assets in the sorted `events` on `network`.
+pub fn movements(events: &[RewardEvent], network: Network) -> Vec<Movement> {
+    let movement = |reward: &Reward, symbol: &str, amount: f64| Movement {
+        date: reward.date,
+        symbol: symbol.to_string(),
+        price_id: reward.asset.price_id.clone(),
+        id: reward.id.clone(),
+        amount,
+        price: reward.price,
+        acquired: Some(reward.date),
+        income: false,
+        staked: false,
+    };
+    let income = |reward: &Reward, symbol: &str| Movement {
+        income: true,
+        ..movement(reward, symbol, units(reward, reward.amount))
+    };
+    let symbol = |reward: &Reward| match reward.asset == network.native_asset() {
+        true => network.native_symbol(),
+        false => network.staking_symbol(),
+    };
+    let payment =
+        |reward: &Reward, amount: U256| movement(reward, symbol(reward), -units(reward, amount));
+
+    // Lots held by the addresses, as `disposals` will hold them, and the lots staked with
+    // each validator, oldest first
+    let mut held: HashMap<String, VecDeque<Lot>> = HashMap::new();
+    let mut staked: HashMap<String, VecDeque<Lot>> = HashMap::new();
+    let mut movements = vec![];
+    for event in events {
+        match event {
+            RewardEvent::ProducedBlock { reward }
+            | RewardEvent::Withdrawal { reward }
+            | RewardEvent::MevReward { reward }
+            | RewardEvent::MevRewardInternal { reward }
+            | RewardEvent::SplitterDistribution { reward }
+            | RewardEvent::MinipoolDistribution { reward }
+            | RewardEvent::SmoothingPoolClaim { reward } => {
+                movements.push(hold(&mut held, income(reward, symbol(reward))))
+            }
+            RewardEvent::TokenReward { reward, token } => {
+                movements.push(hold(&mut held, income(reward, &token.symbol)))
+            }
+            // Rebases accrue in the liquid staking token, not the tracked addresses
+            RewardEvent::LiquidStakingReward { .. } => {}
+            RewardEvent::Refund { reward } | RewardEvent::Unclassified { reward } => movements
+                .push(hold(
+                    &mut held,
+                    movement(reward, symbol(reward), units(reward, reward.amount)),
+                )),
+            RewardEvent::BondReturn { reward } => movements.push(hold(
+                &mut held,
+                Movement {
+                    price: 0.0,
+                    acquired: None,
+                    ..movement(reward, symbol(reward), units(reward, reward.amount))
+                },
+            )),
+            RewardEvent::Deposit { reward, .. } => {
+                let amount = units(reward, reward.amount);
+                let queue = held.entry(reward.asset.price_id.clone()).or_default();
+                let available = queue.iter().fold(0.0, |total, lot| total + lot.amount);
+                let redeposited = amount.min(available);
+                let mut lots = take(queue, redeposited);
+                for lot in lots.iter_mut() {
+                    lot.redeposited = true;
+                }
+                if redeposited > DUST {
+                    movements.push(Movement {
+                        staked: true,
+                        ..movement(reward, symbol(reward), -redeposited)
+                    });
+                }
+                if amount - redeposited > DUST {
+                    lots.push(Lot {
+                        acquired: Some(reward.date),
+                        amount: amount - redeposited,
+                        price: reward.price,
+                        income: false,
+                        redeposited: false,
+                    });
+                }
+                let deposits = staked.entry(reward.id.clone()).or_default();
+                deposits.extend(lots);
+                deposits.make_contiguous().sort_by_key(|lot| lot.acquired);
+            }
+            RewardEvent::PrincipalReturn { reward } => {
+                let deposits = staked.entry(reward.id.clone()).or_default();
+                for lot in take(deposits, units(reward, reward.amount)) {
+                    movements.push(hold(
+                        &mut held,
+                        Movement {
+                            price: lot.price,
+                            acquired: lot.acquired,
+                            income: lot.income,
+                            staked: lot.redeposited,
+                            ..movement(reward, symbol(reward), lot.amount)
+                        },
+                    ));
+                }
+            }
+            RewardEvent::Consolidation {
+                reward,
+                source,
+                fee,
+            } => {
+                let deposits = staked.entry(source.clone()).or_default();
+                let moved = take(deposits, units(reward, reward.amount));
+                let target = staked.entry(reward.id.clone()).or_default();
+                target.extend(moved);
+                target.make_contiguous().sort_by_key(|lot| lot.acquired);
+                movements.push(hold(&mut held, payment(fee, fee.amount)));
+            }
+            RewardEvent::WithdrawalRequest { reward, .. } => {
+                movements.push(hold(&mut held, payment(reward, reward.amount)))
+            }
+            RewardEvent::Outgoing { reward, fee } => {
+                movements.push(hold(&mut held, payment(reward, reward.amount + fee)))
+            }
+        }
+    }
+    movements
+}
+
+/// Applies `movement` to the lots `held`, as `disposals` will.
+fn hold(held: &mut HashMap<String, VecDeque<Lot>>, movement: Movement) -> Movement {
+    let queue = held.entry(movement.price_id.clone()).or_default();
+    match movement.amount > 0.0 {
+        true => insert(queue, Lot::of(&movement)),
+        false => {
+            take(queue, -movement.amount);
+        }
+    }
+    movement
+}
+
+/// Adds `lot` to `queue` in the order of acquisition. Returned principal may have been
+/// acquired before lots already held.
+fn insert(queue: &mut VecDeque<Lot>, lot: Lot) {
+    let index = queue.partition_point(|held| held.acquired <= lot.acquired);
+    queue.insert(index, lot);
+}
+
+/// Takes `amount` units from the oldest lots in `queue`, the remainder beyond them as a
+/// lot of unknown acquisition.
+fn take(queue: &mut VecDeque<Lot>, mut amount: f64) -> Vec<Lot> {
+    let mut taken = vec![];
+    while amount > DUST {
+        let Some(lot) = queue.front_mut() else {
+            taken.push(Lot {
+                acquired: None,
+                amount,
+                price: 0.0,
+                income: false,
+                redeposited: false,
+            });
+            break;
+        };
+        let part = amount.min(lot.amount);
+        taken.push(Lot {
+            amount: part,
+            ..lot.clone()
+        });
+        lot.amount -= part;
+        amount -= part;
+        if lot.amount <= DUST {
+            queue.pop_front();
+        }
+    }
+    taken
+}
+
+/// Disposals among `movements`, split by the lots they are matched to first in, first out.
+/// Under an average cost basis the lots only keep track of the holding period. The same-day
+/// and 30-day rules of [`CostBasis::Section104`] are applied by `uk_report::matches`, here
+/// its pool is an average.
+pub fn disposals(movements: &[Movement], cost_basis: CostBasis) -> Vec<Disposal> {
+    let mut lots: BTreeMap<&str, VecDeque<Lot>> = BTreeMap::new();
+    let mut disposals = vec![];
+
+    for movement in movements {
+        let queue = lots.entry(&movement.price_id).or_default();
+        match (movement.amount > 0.0, movement.staked) {
+            (true, _) => insert(queue, Lot::of(movement)),
+            (false, true) => {
+                take(queue, -movement.amount);
+            }
+            (false, false) => disposals.extend(dispose(queue, movement, cost_basis)),
+        }
+    }
+
+    disposals
+}
+
+/// Takes the units disposed of by `movement` from the oldest lots in `queue`. Units of
+/// unknown acquisition are disposed of at no cost, and left out of the average cost.
+fn dispose(queue: &mut VecDeque<Lot>, movement: &Movement, cost_basis: CostBasis) -> Vec<Disposal> {
+    let known = || queue.iter().filter(|lot| lot.acquired.is_some());
+    let held = known().fold(0.0, |total, lot| total + lot.amount);
+    let average = match held > DUST {
+        true => known().fold(0.0, |total, lot| total + lot.amount * lot.price) / held,
+        false => 0.0,
+    };
+
+    let disposal = |acquired: Option<NaiveDateTime>, amount: f64, price: f64| Disposal {
+        date: movement.date,
+        acquired,
+        symbol: movement.symbol.clone(),
+        price_id: movement.price_id.clone(),
+        id: movement.id.clone(),
+        amount,
+        proceeds: amount * movement.price,
+        cost: amount * price,
+    };
+
+    let mut amount = -movement.amount;
+    let mut disposals = vec![];
+    while amount > DUST {
+        let Some(lot) = queue.front_mut() else {
+            disposals.push(disposal(None, amount, 0.0));
+            break;
+        };
+        let taken = amount.min(lot.amount);
+        let price = match (cost_basis, lot.acquired) {
+            (_, None) | (CostBasis::Fifo, _) => lot.price,
+            (CostBasis::Average | CostBasis::Section104, Some(_)) => average,
+        };
+        disposals.push(disposal(lot.acquired, taken, price));
+        lot.amount -= taken;
+        amount -= taken;
+        if lot.amount <= DUST {
+            queue.pop_front();
+        }
+    }
+    disposals
+}
+
+fn units(reward: &Reward, amount: U256) -> f64 {
+    format_units(amount, reward.asset.decimals)
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+#[test]
+fn test_disposals_match_oldest_lots() {
+    use chrono::NaiveDate;
+    use ethers::utils::parse_ether;
+
+    let date = |year: i32, month: u32| {
+        NaiveDate::from_ymd_opt(year, month, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    };
+    let reward = |date: NaiveDateTime, amount: &str, price: f64| {
+        Reward::priced(
+            1.into(),
+            String::from("0x01"),
+            date,
+            parse_ether(amount).unwrap(),
+            Network::Mainnet.native_asset(),
+            ethers::types::Address::zero(),
+            price,
+        )
+    };
+    let events = vec![
+        RewardEvent::Withdrawal {
+            reward: reward(date(2022, 1), "1", 1000.0),
+        },
+        RewardEvent::MevReward {
+            reward: reward(date(2023, 6), "1", 2000.0),
+        },
+        RewardEvent::Outgoing {
+            reward: reward(date(2023, 9), "1.4", 1500.0),
+            fee: parse_ether("0.1").unwrap(),
+        },
+        RewardEvent::Outgoing {
+            reward: reward(date(2023, 10), "1", 1500.0),
+            fee: U256::zero(),
+        },
+    ];
+
+    let disposals = disposals(&movements(&events, Network::Mainnet), CostBasis::Fifo);
+    let summary: Vec<(Option<NaiveDateTime>, f64, f64)> = disposals
+        .iter()
+        .map(|disposal| {
+            let round = |value: f64| (value * 1e6).round() / 1e6;
+            (
+                disposal.acquired,
+                round(disposal.amount),
+                round(disposal.gain()),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (Some(date(2022, 1)), 1.0, 500.0),
+            (Some(date(2023, 6)), 0.5, -250.0),
+            (Some(date(2023, 6)), 0.5, -250.0),
+            (None, 0.5, 750.0),
+        ]
+    );
+
+    // Both lots at an average of 1500 for the first transfer
+    let average = crate::lots::disposals(&movements(&events, Network::Mainnet), CostBasis::Average);
+    assert_eq!(average[0].gain(), 0.0);
+    assert_eq!(average[1].cost, 750.0);
+}
+
+#[test]
+fn test_returned_principal_opens_lots() {
+    use chrono::NaiveDate;
+    use ethers::utils::parse_ether;
+
+    let date = |year: i32, month: u32| {
+        NaiveDate::from_ymd_opt(year, month, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    };
+    let reward = |id: &str, date: NaiveDateTime, amount: &str, price: f64| {
+        Reward::priced(
+            1.into(),
+            String::from(id),
+            date,
+            parse_ether(amount).unwrap(),
+            Network::Mainnet.native_asset(),
+            ethers::types::Address::zero(),
+            price,
+        )
+    };
+    let events = vec![
+        RewardEvent::Deposit {
+            reward: reward("1", date(2021, 1), "32", 1000.0),
+            pubkey: String::from("0x01"),
+        },
+        RewardEvent::Withdrawal {
+            reward: reward("1", date(2023, 6), "1", 2000.0),
+        },
+        RewardEvent::BondReturn {
+            reward: reward("0x02", date(2023, 7), "8", 1800.0),
+        },
+        // 2 ETH more than deposited, deposited before the tracked history
+        RewardEvent::PrincipalReturn {
+            reward: reward("1", date(2023, 8), "34", 1800.0),
+        },
+        RewardEvent::Outgoing {
+            reward: reward("", date(2023, 9), "12", 1500.0),
+            fee: U256::zero(),
+        },
+    ];
+
+    let summary: Vec<(Option<NaiveDateTime>, f64, f64)> =
+        disposals(&movements(&events, Network::Mainnet), CostBasis::Fifo)
+            .iter()
+            .map(|disposal| (disposal.acquired, disposal.amount, disposal.cost))
+            .collect();
+    // Units of unknown acquisition first, then the deposit, leaving the reward
+    assert_eq!(
+        summary,
+        vec![
+            (None, 8.0, 0.0),
+            (None, 2.0, 0.0),
+            (Some(date(2021, 1)), 2.0, 2000.0),
+        ]
+    );
+}
+
+#[test]
+fn test_redeposited_rewards_are_staked() {
+    use chrono::NaiveDate;
+    use ethers::utils::parse_ether;
+
+    let date = |year: i32, month: u32| {
+        NaiveDate::from_ymd_opt(year, month, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    };
+    let reward = |id: &str, date: NaiveDateTime, amount: &str, price: f64| {
+        Reward::priced(
+            1.into(),
+            String::from(id),
+            date,
+            parse_ether(amount).unwrap(),
+            Network::Mainnet.native_asset(),
+            ethers::types::Address::zero(),
+            price,
+        )
+    };
+    let events = vec![
+        RewardEvent::Withdrawal {
+            reward: reward("1", date(2022, 1), "2", 1000.0),
+        },
+        RewardEvent::MevReward {
+            reward: reward("0x01", date(2022, 6), "1", 2000.0),
+        },
+        // 3 ETH of rewards staked again, 29 ETH funded from elsewhere
+        RewardEvent::Deposit {
+            reward: reward("2", date(2022, 7), "32", 1500.0),
+            pubkey: String::from("0x02"),
+        },
+        RewardEvent::Outgoing {
+            reward: reward("0x03", date(2022, 8), "1", 1500.0),
+            fee: U256::zero(),
+        },
+        RewardEvent::PrincipalReturn {
+            reward: reward("2", date(2023, 8), "32", 1800.0),
+        },
+        RewardEvent::Outgoing {
+            reward: reward("0x04", date(2023, 9), "32", 1500.0),
+            fee: U256::zero(),
+        },
+    ];
+
+    let movements = movements(&events, Network::Mainnet);
+    let staked: Vec<f64> = movements
+        .iter()
+        .filter(|movement| movement.staked)
+        .map(|movement| movement.amount)
+        .collect();
+    assert_eq!(staked, vec![-3.0, 2.0, 1.0]);
+
+    let summary: Vec<(Option<NaiveDateTime>, f64, f64)> = disposals(&movements, CostBasis::Fifo)
+        .iter()
+        .map(|disposal| (disposal.acquired, disposal.amount, disposal.cost))
+        .collect();
+    // The staked rewards aren't disposed of while staked, and come back at their own cost
+    assert_eq!(
+        summary,
+        vec![
+            (None, 1.0, 0.0),
+            (Some(date(2022, 1)), 2.0, 2000.0),
+            (Some(date(2022, 6)), 1.0, 2000.0),
+            (Some(date(2022, 7)), 29.0, 43500.0),
+        ]
+    );
+}
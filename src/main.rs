@@ -1,146 +1,129 @@
-#![allow(non_snake_case)]
-// import the prelude to get access to the `rsx!` macro and the `Scope` and `Element` types
-
-mod addresses;
-mod conversion;
-mod etherscan;
-mod processing;
-mod types;
-
-use crate::processing::{
-    current_balance, process_transactions, range_filter, total_earnings, unliquidated,
-};
-use crate::types::{Reward, RewardEvent};
-use csv::Writer;
-use ethers::types::U256;
+use chrono::Utc;
 use ethers::utils::format_ether;
-use std::fs::File;
-
-async fn proc() {
-    let mut events = process_transactions().await;
-    events.sort();
-
-    let year = 2023;
-
-    let filtered_events: Vec<&RewardEvent> = events
-        .iter()
-        .filter(|event| match event {
-            RewardEvent::Withdrawal { reward } => range_filter(&reward.date, year),
-            RewardEvent::ProducedBlock { reward } => range_filter(&reward.date, year),
-            RewardEvent::MevReward { reward } => range_filter(&reward.date, year),
-            RewardEvent::MevRewardInternal { reward, .. } => range_filter(&reward.date, year),
-            RewardEvent::Outgoing { reward, .. } => range_filter(&reward.date, year),
-        })
-        .collect();
-
-    let mut execution_writer =
-        csv::Writer::from_path(format!("Execution Rewards {}.csv", year)).unwrap();
-    let mut consensus_writer =
-        csv::Writer::from_path(format!("Consensus Rewards {}.csv", year)).unwrap();
-    let mut fees_writer = csv::Writer::from_path(format!("Fees {}.csv", year)).unwrap();
-
-    let header = ["Date", "Block", "Type", "ID", "ETH", "ETH_EUR_Price", "EUR"];
-    execution_writer.write_record(header).unwrap();
-    consensus_writer.write_record(header).unwrap();
-    fees_writer.write_record(header).unwrap();
+use staking_tax::addresses::Config;
+use staking_tax::pricing::Pricer;
+use staking_tax::processing::{current_balance, process_transactions, unliquidated};
+use staking_tax::reports::{write_reports, ReportFormat};
+use staking_tax::tax_profile::TaxProfile;
+use std::path::Path;
+
+/// Command line arguments.
+struct Arguments {
+    /// Report formats given as e.g. `--format csv,json,xlsx,md,html,pdf`, CSV and XLSX by
+    /// default. Exports for tax tools and ledgers are `koinly`, `cointracking`, `blockpit`,
+    /// `beancount`, `hledger` and `datev`, tax figures are `anlage_so`, `us` and `uk`.
+    formats: Vec<ReportFormat>,
+    /// Year the reported fiscal year starts in, given as `--year 2023`. Defaults to the
+    /// last completed fiscal year of the tax profile.
+    year: Option<i32>,
+}
 
-    let mut execution_rows = 1;
-    let mut consensus_rows = 1;
-    let mut fees_rows = 1;
-    for r in filtered_events {
-        match r {
-            RewardEvent::Withdrawal { reward } => {
-                write_reward(&mut consensus_writer, reward, "Withdrawal");
-                consensus_rows += 1;
+const USAGE: &str = "Usage: staking-tax [--format csv,json,xlsx,md,html,pdf,koinly,cointracking,\
+                     blockpit,beancount,hledger,datev,anlage_so,us,uk] [--year YEAR]";
+
+fn arguments() -> Result<Arguments, String> {
+    let mut args = std::env::args().skip(1);
+    let mut arguments = Arguments {
+        formats: vec![],
+        year: None,
+    };
+    while let Some(arg) = args.next() {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name.to_string(), value.to_string()),
+            None => {
+                let value = args.next().ok_or(format!("{} needs a value", arg))?;
+                (arg, value)
             }
-            RewardEvent::ProducedBlock { reward } => {
-                write_reward(&mut execution_writer, reward, "Block");
-                execution_rows += 1;
+        };
+        match name.as_str() {
+            "--format" => {
+                for format in value.split(',') {
+                    arguments.formats.push(format.parse()?);
+                }
             }
-            RewardEvent::MevReward { reward } => {
-                write_reward(&mut execution_writer, reward, "MevReward");
-                execution_rows += 1;
-            }
-            RewardEvent::MevRewardInternal { reward } => {
-                write_reward(&mut execution_writer, reward, "MevRewardInternal");
-                execution_rows += 1;
-            }
-            RewardEvent::Outgoing { reward, fee } => {
-                write_fee(&mut fees_writer, reward, *fee, "Fee");
-                fees_rows += 1;
+            "--year" => {
+                let year = value
+                    .parse()
+                    .map_err(|_| format!("Invalid year {}", value))?;
+                arguments.year = Some(year);
             }
+            _ => return Err(format!("Unknown argument {}", name)),
         }
     }
+    if arguments.formats.is_empty() {
+        arguments.formats = vec![ReportFormat::Csv, ReportFormat::Xlsx];
+    }
+    Ok(arguments)
+}
+
+/// Fetches, prices and reports the events, failing with a message for stderr.
+async fn proc(arguments: &Arguments) -> Result<(), String> {
+    let config = Config::from_env().map_err(|error| format!("Invalid configuration: {}", error))?;
+    let profile =
+        TaxProfile::from_env().map_err(|error| format!("Invalid tax profile: {}", error))?;
+    let pricer = Pricer::from_env().map_err(|error| format!("Invalid pricing: {}", error))?;
+
+    let mut events = process_transactions(&config)
+        .await
+        .map_err(|error| format!("Could not fetch chain data: {}", error))?;
+
+    let year = arguments.year.unwrap_or_else(|| {
+        let today = profile.local(&Utc::now().naive_utc()).date();
+        profile.last_completed_year(today)
+    });
+    pricer
+        .price_events(&mut events)
+        .await
+        .map_err(|error| format!("Could not resolve prices: {}", error))?;
+    let currency = pricer.currency.to_string();
+    events.sort();
 
-    execution_writer
-        .write_record(footer(execution_rows))
-        .unwrap();
-    consensus_writer
-        .write_record(footer(consensus_rows))
-        .unwrap();
-    fees_writer.write_record(footer(fees_rows)).unwrap();
+    let network = config.network;
+    if network.is_testnet() {
+        println!(
+            "Dry run on {:?}, amounts are priced as mainnet ETH",
+            network
+        );
+    }
 
-    execution_writer.flush().unwrap();
-    consensus_writer.flush().unwrap();
-    fees_writer.flush().unwrap();
+    write_reports(
+        &events,
+        year,
+        &config,
+        &pricer,
+        &profile,
+        Path::new("."),
+        &arguments.formats,
+    )
+    .map_err(|error| format!("Could not write reports: {}", error))?;
 
     println!(
-        "Current Balance: {} ETH",
-        format_ether(current_balance(&events))
+        "Current Balance: {} {}",
+        format_ether(current_balance(&events)),
+        network.native_symbol()
     );
 
     println!(
-        "Sum: {} EUR, unliquidated: {} EUR",
-        total_earnings(&events),
+        "Income: {} {}, unliquidated: {} {}",
+        profile.income(&events),
+        currency,
         unliquidated(&events),
+        currency,
     );
-}
-
-fn footer(rows: i32) -> [&'static str; 7] {
-    let fee_eth = format!("=SUM(E2:E{})", rows);
-    let fee_eur = format!("=SUM(G2:G{})", rows);
-    [
-        "",
-        "",
-        "",
-        "",
-        Box::leak(fee_eth.into_boxed_str()),
-        "",
-        Box::leak(fee_eur.into_boxed_str()),
-    ]
-}
-
-fn write_fee(fees_writer: &mut Writer<File>, reward: &Reward, fee: U256, type_name: &str) {
-    let price = reward.price;
-    let fee = format_ether(fee).parse::<f64>().unwrap();
-    fees_writer
-        .write_record([
-            reward.date.to_string(),
-            reward.block.to_string(),
-            type_name.to_string(),
-            reward.id.to_string(),
-            format!("{:.8}", fee),
-            price.to_string(),
-            (fee * price).to_string(),
-        ])
-        .unwrap();
-}
-
-fn write_reward(writer: &mut Writer<File>, reward: &Reward, type_name: &str) {
-    writer
-        .write_record([
-            reward.date.to_string(),
-            reward.block.to_string(),
-            type_name.to_string(),
-            reward.id.to_string(),
-            format!("{:.8}", format_ether(reward.amount)),
-            reward.price.to_string(),
-            reward.fiat.to_string(),
-        ])
-        .unwrap();
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() {
-    proc().await;
+    let arguments = match arguments() {
+        Ok(arguments) => arguments,
+        Err(error) => {
+            eprintln!("{}\n{}", error, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if let Err(error) = proc(&arguments).await {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,135 @@
+//! Report as a Markdown document with a table per section and a summary.
+
+use crate::reports::{Cell, Report, ReportWriter};
+use std::error::Error;
+use std::fmt::Write;
+use std::path::Path;
+
+pub struct MarkdownReport;
+
+impl ReportWriter for MarkdownReport {
+    fn write(&self, report: &Report, directory: &Path) -> Result<(), Box<dyn Error>> {
+        let path = directory.join(format!("Staking Rewards {}.md", report.year));
+        std::fs::write(path, render(report))?;
+        Ok(())
+    }
+}
+
+fn render(report: &Report) -> String {
+    let mut markdown = format!("# Staking Rewards {}\n", report.year);
+
+    writeln!(markdown, "\n## Summary\n").unwrap();
+    writeln!(markdown, "| Report | {} |", report.currency).unwrap();
+    writeln!(markdown, "| --- | ---: |").unwrap();
+    for (section, total) in report.summary() {
+        writeln!(markdown, "| {} | {:.2} |", section.title, total).unwrap();
+    }
+    writeln!(markdown, "| **Net** | **{:.2}** |", report.net()).unwrap();
+
+    for section in &report.sections {
+        writeln!(markdown, "\n## {}\n", section.title).unwrap();
+        writeln!(markdown, "| {} |", section.columns.join(" | ")).unwrap();
+        let alignment: Vec<&str> = match section.rows.first() {
+            Some(row) => row
+                .iter()
+                .map(|cell| match cell.value() {
+                    Some(_) => "---:",
+                    None => "---",
+                })
+                .collect(),
+            None => vec!["---"; section.columns.len()],
+        };
+        writeln!(markdown, "| {} |", alignment.join(" | ")).unwrap();
+        for row in &section.rows {
+            let cells: Vec<String> = row.iter().map(format_cell).collect();
+            writeln!(markdown, "| {} |", cells.join(" | ")).unwrap();
+        }
+        if !section.totals.is_empty() {
+            let totals: Vec<String> = (0..section.columns.len())
+                .map(|column| match (column, section.totals.contains(&column)) {
+                    (0, _) => String::from("**Total**"),
+                    (_, true) => {
+                        let total = match section.amount_total() == Some(column) {
+                            true => Cell::Amount(section.total(column)),
+                            false => Cell::Fiat(section.total(column)),
+                        };
+                        format!("**{}**", total)
+                    }
+                    (_, false) => String::new(),
+                })
+                .collect();
+            writeln!(markdown, "| {} |", totals.join(" | ")).unwrap();
+        }
+    }
+
+    markdown
+}
+
+fn format_cell(cell: &Cell) -> String {
+    // Pipes would end the cell
+    cell.to_string().replace('|', "\\|")
+}
+
+#[test]
+fn test_markdown_report() {
+    use crate::reports::{Role, Section};
+
+    let report = Report {
+        year: 2023,
+        currency: String::from("EUR"),
+        sections: vec![
+            Section {
+                title: String::from("Rewards 2023"),
+                role: Role::Income,
+                columns: ["Type", "ETH", "EUR"].map(String::from).to_vec(),
+                rows: vec![
+                    vec![
+                        Cell::Text(String::from("Block")),
+                        Cell::Amount(0.05),
+                        Cell::Fiat(76.006),
+                    ],
+                    vec![
+                        Cell::Text(String::from("MevReward")),
+                        Cell::Amount(0.1),
+                        Cell::Fiat(152.012),
+                    ],
+                ],
+                totals: vec![1, 2],
+            },
+            Section {
+                title: String::from("Principal"),
+                role: Role::Other,
+                columns: ["Validator", "Deposited_EUR"].map(String::from).to_vec(),
+                rows: vec![],
+                totals: vec![],
+            },
+        ],
+        ..Default::default()
+    };
+
+    assert_eq!(
+        render(&report),
+        "# Staking Rewards 2023
+
+## Summary
+
+| Report | EUR |
+| --- | ---: |
+| Rewards 2023 | 228.02 |
+| **Net** | **228.02** |
+
+## Rewards 2023
+
+| Type | ETH | EUR |
+| --- | ---: | ---: |
+| Block | 0.05000000 | 76.01 |
+| MevReward | 0.10000000 | 152.01 |
+| **Total** | **0.15000000** | **228.02** |
+
+## Principal
+
+| Validator | Deposited_EUR |
+| --- | --- |
+"
+    );
+}
@@ -0,0 +1,205 @@
+//! Supported networks and their assets, endpoints and units.
+
+use crate::types::Asset;
+use dotenv::dotenv;
+use ethers::types::{Address, U256};
+use std::env;
+use std::str::FromStr;
+
+/// The network the reward addresses live on, selected with `NETWORK` (defaults to mainnet).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Network {
+    #[default]
+    /// Ethereum mainnet.
+    Mainnet,
+    /// Gnosis Chain, staking GNO with xDAI as the native asset.
+    Gnosis,
+    /// Holesky testnet.
+    Holesky,
+    /// Hoodi testnet.
+    Hoodi,
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mainnet" | "ethereum" => Ok(Network::Mainnet),
+            "gnosis" => Ok(Network::Gnosis),
+            "holesky" => Ok(Network::Holesky),
+            "hoodi" => Ok(Network::Hoodi),
+            _ => Err(format!("Unsupported network {}", s)),
+        }
+    }
+}
+
+impl Network {
+    /// Reads `NETWORK`, mainnet if unset or empty.
+    pub fn from_env() -> Result<Self, String> {
+        dotenv().ok();
+
+        Self::from_var(env::var("NETWORK").ok())
+    }
+
+    fn from_var(value: Option<String>) -> Result<Self, String> {
+        match value.as_deref().map(str::trim) {
+            None | Some("") => Ok(Network::Mainnet),
+            Some(network) => network.parse(),
+        }
+    }
+
+    /// EIP-155 chain id, also selecting the network on Etherscan.
+    pub fn chain_id(&self) -> u64 {
+        match self {
+            Network::Mainnet => 1,
+            Network::Gnosis => 100,
+            Network::Holesky => 17000,
+            Network::Hoodi => 560048,
+        }
+    }
+
+    /// Whether assets on the network have no market value of their own.
+    pub fn is_testnet(&self) -> bool {
+        matches!(self, Network::Holesky | Network::Hoodi)
+    }
+
+    /// Public RPC endpoint used if neither `RPC_URL` nor `INFURA_API_KEY` is configured.
+    pub fn public_rpc_url(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "https://ethereum-rpc.publicnode.com",
+            Network::Gnosis => "https://rpc.gnosischain.com",
+            Network::Holesky => "https://ethereum-holesky-rpc.publicnode.com",
+            Network::Hoodi => "https://ethereum-hoodi-rpc.publicnode.com",
+        }
+    }
+
+    /// Symbol of the execution layer asset, in which gas and execution rewards are paid.
+    pub fn native_symbol(&self) -> &'static str {
+        match self {
+            Network::Gnosis => "xDAI",
+            _ => "ETH",
+        }
+    }
+
+    /// Symbol of the staked asset, in which consensus rewards are paid.
+    pub fn staking_symbol(&self) -> &'static str {
+        match self {
+            Network::Gnosis => "GNO",
+            _ => "ETH",
+        }
+    }
+
+    /// CoinGecko id pricing the native asset. Testnet assets are priced as mainnet ETH
+    /// so dry runs produce realistic reports.
+    pub fn native_price_id(&self) -> &'static str {
+        match self {
+            Network::Gnosis => "xdai",
+            _ => "ethereum",
+        }
+    }
+
+    /// CoinGecko id pricing the staked asset.
+    pub fn staking_price_id(&self) -> &'static str {
+        match self {
+            Network::Gnosis => "gnosis",
+            _ => "ethereum",
+        }
+    }
+
+    /// Asset gas and execution rewards are paid in.
+    pub fn native_asset(&self) -> Asset {
+        Asset {
+            price_id: self.native_price_id().to_string(),
+            decimals: 18,
+        }
+    }
+
+    /// Asset validators stake and consensus rewards are paid in.
+    pub fn staking_asset(&self) -> Asset {
+        Asset {
+            price_id: self.staking_price_id().to_string(),
+            decimals: 18,
+        }
+    }
+
+    /// Converts a consensus layer amount in Gwei to wei of the staked asset.
+    /// Gnosis Chain validators account in mGNO, 32 of which make up one GNO.
+    pub fn consensus_to_wei(&self, gwei: U256) -> U256 {
+        match self {
+            Network::Gnosis => U256::exp10(9) * gwei / 32,
+            _ => U256::exp10(9) * gwei,
+        }
+    }
+
+    /// Principal of a single validator funded with the minimum activation balance.
+    pub fn validator_principal(&self) -> U256 {
+        self.consensus_to_wei(U256::from(32_000_000_000u64))
+    }
+
+    /// Address of the beacon deposit contract.
+    pub fn deposit_contract(&self) -> Address {
+        match self {
+            Network::Mainnet | Network::Hoodi => "0x00000000219ab540356cbb839cbe05303d7705fa",
+            Network::Gnosis => "0x0b98057ea310f4d31f2a452b414647007d1645d9",
+            Network::Holesky => "0x4242424242424242424242424242424242424242",
+        }
+        .parse()
+        .unwrap()
+    }
+
+    /// Unix time of the beacon chain genesis.
+    pub fn genesis_time(&self) -> i64 {
+        match self {
+            Network::Mainnet => 1606824023,
+            Network::Gnosis => 1638993340,
+            Network::Holesky => 1695902400,
+            Network::Hoodi => 1742213400,
+        }
+    }
+
+    /// Length of a beacon chain slot in seconds.
+    pub fn seconds_per_slot(&self) -> i64 {
+        match self {
+            Network::Gnosis => 5,
+            _ => 12,
+        }
+    }
+
+    /// Slot of the beacon chain at the unix `timestamp`.
+    pub fn slot_at(&self, timestamp: i64) -> i64 {
+        (timestamp - self.genesis_time()) / self.seconds_per_slot()
+    }
+}
+
+#[test]
+fn test_consensus_to_wei() {
+    let gwei = U256::from(32_000_000_000u64);
+    assert_eq!(
+        Network::Mainnet.consensus_to_wei(gwei),
+        U256::exp10(18) * 32
+    );
+    assert_eq!(Network::Gnosis.consensus_to_wei(gwei), U256::exp10(18));
+    assert_eq!("Hoodi".parse::<Network>(), Ok(Network::Hoodi));
+}
+
+#[test]
+fn test_network_from_var() {
+    assert_eq!(Network::from_var(None), Ok(Network::Mainnet));
+    assert_eq!(Network::from_var(Some(String::new())), Ok(Network::Mainnet));
+    assert_eq!(
+        Network::from_var(Some(String::from(" gnosis "))),
+        Ok(Network::Gnosis)
+    );
+    assert_eq!(
+        Network::from_var(Some(String::from("goerli"))),
+        Err(String::from("Unsupported network goerli"))
+    );
+}
+
+#[test]
+fn test_slot_at() {
+    assert_eq!(Network::Mainnet.slot_at(1606824023), 0);
+    assert_eq!(Network::Mainnet.slot_at(1700000003), 7764665);
+    assert_eq!(Network::Gnosis.slot_at(1638993345), 1);
+}
@@ -0,0 +1,397 @@
+//! Annual report as a PDF document for the accountant, drawn with the PDF base fonts.
+//!
+//! Tables are set in Courier, whose fixed width lets columns be laid out by character
+//! count without font metrics.
+
+use crate::reports::{Cell, Report, ReportWriter, Section};
+use printpdf::{
+    BuiltinFont, Color, IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference,
+    PdfLayerReference, Point, Rgb,
+};
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+/// A4 landscape, leaving room for transaction hashes.
+const PAGE_WIDTH: f32 = 297.0;
+const PAGE_HEIGHT: f32 = 210.0;
+const MARGIN: f32 = 15.0;
+/// Millimetres per point.
+const POINT: f32 = 0.3528;
+/// Width of a Courier character, relative to the font size.
+const COURIER_WIDTH: f32 = 0.6;
+/// Approximate average width of a Helvetica character, relative to the font size.
+const HELVETICA_WIDTH: f32 = 0.5;
+const TEXT_SIZE: f32 = 10.0;
+const MAX_TABLE_SIZE: f32 = 9.0;
+const CHART_HEIGHT: f32 = 70.0;
+
+pub struct PdfReport;
+
+impl ReportWriter for PdfReport {
+    fn write(&self, report: &Report, directory: &Path) -> Result<(), Box<dyn Error>> {
+        let path = directory.join(format!("Annual Report {}.pdf", report.year));
+        let document = render(report)?;
+        document.save(&mut BufWriter::new(File::create(path)?))?;
+        Ok(())
+    }
+}
+
+/// Pages written top to bottom, starting a new page when the current one is full.
+struct Pages {
+    document: PdfDocumentReference,
+    layer: PdfLayerReference,
+    /// Baseline of the next line, from the bottom of the page.
+    y: f32,
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+    mono: IndirectFontRef,
+    mono_bold: IndirectFontRef,
+}
+
+impl Pages {
+    fn new(title: &str) -> Result<Self, printpdf::Error> {
+        let (document, page, layer) =
+            PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Content");
+        let layer = document.get_page(page).get_layer(layer);
+        Ok(Pages {
+            regular: document.add_builtin_font(BuiltinFont::Helvetica)?,
+            bold: document.add_builtin_font(BuiltinFont::HelveticaBold)?,
+            mono: document.add_builtin_font(BuiltinFont::Courier)?,
+            mono_bold: document.add_builtin_font(BuiltinFont::CourierBold)?,
+            document,
+            layer,
+            y: PAGE_HEIGHT - MARGIN,
+        })
+    }
+
+    fn new_page(&mut self) {
+        let (page, layer) = self
+            .document
+            .add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Content");
+        self.layer = self.document.get_page(page).get_layer(layer);
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    /// Starts a new page unless `height` fits on the current one.
+    fn reserve(&mut self, height: f32) {
+        if self.y - height < MARGIN {
+            self.new_page();
+        }
+    }
+
+    fn line(&mut self, text: &str, size: f32, x: f32, font: &IndirectFontRef) {
+        self.layer.use_text(text, size, Mm(x), Mm(self.y), font);
+    }
+
+    fn heading(&mut self, text: &str, size: f32) {
+        // Keep headings together with a few lines of what follows
+        self.reserve(size * POINT * 2.0 + 4.0 * TEXT_SIZE * POINT * 1.4);
+        self.y -= size * POINT * 0.8;
+        let bold = self.bold.clone();
+        self.line(text, size, MARGIN, &bold);
+        self.y -= size * POINT * 1.2;
+    }
+
+    fn paragraph(&mut self, text: &str) {
+        let line_height = TEXT_SIZE * POINT * 1.4;
+        let width = ((PAGE_WIDTH - 2.0 * MARGIN) / (TEXT_SIZE * HELVETICA_WIDTH * POINT)) as usize;
+        let regular = self.regular.clone();
+        for line in wrap(text, width) {
+            self.reserve(line_height);
+            self.line(&line, TEXT_SIZE, MARGIN, &regular);
+            self.y -= line_height;
+        }
+        self.y -= line_height * 0.5;
+    }
+
+    /// Table of `rows`, right aligning the `numeric` columns and repeating the header on
+    /// each page. A `total` row is set in bold below a rule.
+    fn table(
+        &mut self,
+        columns: &[String],
+        rows: &[Vec<String>],
+        numeric: &[bool],
+        total: Option<Vec<String>>,
+    ) {
+        let widths: Vec<usize> = (0..columns.len())
+            .map(|column| {
+                rows.iter()
+                    .chain(total.iter())
+                    .map(|row| row[column].len())
+                    .chain([columns[column].len()])
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+        let characters = widths.iter().sum::<usize>() + 2 * (columns.len() - 1);
+        let size = ((PAGE_WIDTH - 2.0 * MARGIN) / (characters as f32 * COURIER_WIDTH * POINT))
+            .min(MAX_TABLE_SIZE);
+        let character = size * COURIER_WIDTH * POINT;
+        let line_height = size * POINT * 1.4;
+
+        let fonts = (self.mono.clone(), self.mono_bold.clone());
+        let write_row = |pages: &mut Pages, row: &[String], font: &IndirectFontRef| {
+            let mut x = MARGIN;
+            for (column, text) in row.iter().enumerate() {
+                let offset = match numeric[column] {
+                    true => (widths[column] - text.len()) as f32 * character,
+                    false => 0.0,
+                };
+                pages.line(text, size, x + offset, font);
+                x += (widths[column] + 2) as f32 * character;
+            }
+            pages.y -= line_height;
+        };
+        let header = |pages: &mut Pages| {
+            write_row(pages, columns, &fonts.1);
+            pages.rule(pages.y + line_height * 0.6);
+        };
+
+        self.reserve(line_height * 3.0);
+        header(self);
+        for row in rows {
+            if self.y - line_height < MARGIN {
+                self.new_page();
+                header(self);
+            }
+            write_row(self, row, &fonts.0);
+        }
+        if let Some(total) = total {
+            self.reserve(line_height * 2.0);
+            self.rule(self.y + line_height * 0.6);
+            write_row(self, &total, &fonts.1);
+        }
+        self.y -= line_height;
+    }
+
+    /// Horizontal rule across the page at `y`.
+    fn rule(&self, y: f32) {
+        self.layer.set_outline_thickness(0.5);
+        self.layer.add_line(Line {
+            points: vec![
+                (Point::new(Mm(MARGIN), Mm(y)), false),
+                (Point::new(Mm(PAGE_WIDTH - MARGIN), Mm(y)), false),
+            ],
+            is_closed: false,
+        });
+    }
+
+    /// Step line of the balance over the year with its axes, month ticks and maximum.
+    fn balance_chart(&mut self, report: &Report) {
+        let steps = report.balance_steps();
+        let max = steps
+            .iter()
+            .map(|(_, balance)| *balance)
+            .fold(0.0, f64::max);
+        let scale = if max > 0.0 { max } else { 1.0 };
+        let label_width = 20.0;
+        let width = PAGE_WIDTH - 2.0 * MARGIN - label_width;
+
+        self.reserve(CHART_HEIGHT + 10.0);
+        let top = self.y;
+        let bottom = top - CHART_HEIGHT;
+        let left = MARGIN + label_width;
+        let point = |fraction: f64, balance: f64| {
+            Point::new(
+                Mm(left + fraction as f32 * width),
+                Mm(bottom + (balance / scale) as f32 * CHART_HEIGHT),
+            )
+        };
+
+        self.layer.set_outline_thickness(0.5);
+        self.layer.set_outline_color(black());
+        self.layer.add_line(Line {
+            points: vec![
+                (point(0.0, scale), false),
+                (point(0.0, 0.0), false),
+                (point(1.0, 0.0), false),
+            ],
+            is_closed: false,
+        });
+
+        let regular = self.regular.clone();
+        self.y = top - 2.0;
+        self.line(&format!("{:.2}", max), 8.0, MARGIN, &regular);
+        self.y = bottom;
+        self.line("0", 8.0, MARGIN, &regular);
+        self.y = bottom - 5.0;
+        for month in 0..12 {
+            let label = report.month(month).name();
+            let x = left + month as f32 / 12.0 * width;
+            self.line(&label[..3], 8.0, x, &regular);
+        }
+
+        self.layer.set_outline_thickness(1.5);
+        self.layer
+            .set_outline_color(Color::Rgb(Rgb::new(0.12, 0.44, 0.92, None)));
+        self.layer.add_line(Line {
+            points: steps
+                .iter()
+                .map(|(fraction, balance)| (point(*fraction, *balance), false))
+                .collect(),
+            is_closed: false,
+        });
+        self.layer.set_outline_color(black());
+        self.y = bottom - 12.0;
+    }
+}
+
+fn black() -> Color {
+    Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None))
+}
+
+fn render(report: &Report) -> Result<PdfDocumentReference, printpdf::Error> {
+    let title = format!("Staking Rewards {}", report.year);
+    let mut pages = Pages::new(&title)?;
+
+    pages.heading(&title, 24.0);
+    pages.paragraph(&format!(
+        "Annual report of staking rewards, valued in {}.",
+        report.currency
+    ));
+    pages.heading("Addresses", 14.0);
+    let addresses: Vec<Vec<String>> = report
+        .addresses
+        .iter()
+        .map(|(role, address)| vec![role.clone(), address.clone()])
+        .collect();
+    pages.table(
+        &["Role", "Address"].map(String::from),
+        &addresses,
+        &[false, false],
+        None,
+    );
+    pages.heading("Methodology", 14.0);
+    for paragraph in &report.methodology {
+        pages.paragraph(paragraph);
+    }
+
+    pages.new_page();
+    pages.heading("Summary", 14.0);
+    let summary: Vec<Vec<String>> = report
+        .summary()
+        .iter()
+        .map(|(section, total)| vec![section.title.clone(), format!("{:.2}", total)])
+        .collect();
+    pages.table(
+        &[String::from("Report"), report.currency.clone()],
+        &summary,
+        &[false, true],
+        Some(vec![String::from("Net"), format!("{:.2}", report.net())]),
+    );
+    pages.heading(&format!("Balance ({})", report.balance_symbol), 14.0);
+    pages.balance_chart(report);
+
+    for section in &report.sections {
+        // Start short sections on a new page rather than splitting them
+        let height =
+            14.0 * POINT * 2.0 + (section.rows.len() + 3) as f32 * MAX_TABLE_SIZE * POINT * 1.4;
+        if height < PAGE_HEIGHT - 2.0 * MARGIN {
+            pages.reserve(height);
+        }
+        pages.heading(&section.title, 14.0);
+        write_section(&mut pages, section);
+    }
+
+    Ok(pages.document)
+}
+
+fn write_section(pages: &mut Pages, section: &Section) {
+    let rows: Vec<Vec<String>> = section
+        .rows
+        .iter()
+        .map(|row| row.iter().map(Cell::to_string).collect())
+        .collect();
+    let numeric: Vec<bool> = (0..section.columns.len())
+        .map(|column| {
+            section
+                .rows
+                .first()
+                .and_then(|row| row.get(column))
+                .is_some_and(|cell| cell.value().is_some())
+        })
+        .collect();
+    let total = (!section.totals.is_empty()).then(|| {
+        (0..section.columns.len())
+            .map(|column| match (column, section.totals.contains(&column)) {
+                (0, _) => String::from("Total"),
+                (_, true) if section.amount_total() == Some(column) => {
+                    Cell::Amount(section.total(column)).to_string()
+                }
+                (_, true) => Cell::Fiat(section.total(column)).to_string(),
+                (_, false) => String::new(),
+            })
+            .collect()
+    });
+    pages.table(&section.columns, &rows, &numeric, total);
+}
+
+/// Splits `text` into lines of at most `width` characters at spaces.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[tokio::test]
+async fn test_write_pdf() {
+    crate::fixtures::setup();
+
+    let config = crate::addresses::Config::from_env().unwrap();
+    let mut events = crate::processing::process_transactions(&config)
+        .await
+        .unwrap();
+    let pricer = crate::pricing::Pricer::default();
+    pricer.price_events(&mut events).await.unwrap();
+    events.sort();
+
+    let directory =
+        std::env::temp_dir().join(format!("staking-tax-test-pdf-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    PdfReport
+        .write(
+            &Report::new(
+                &events,
+                2023,
+                &config,
+                &pricer,
+                &crate::tax_profile::TaxProfile::default(),
+            ),
+            &directory,
+        )
+        .unwrap();
+
+    let pdf = lopdf::Document::load(directory.join("Annual Report 2023.pdf")).unwrap();
+    let pages: Vec<u32> = pdf.get_pages().into_keys().collect();
+    assert_eq!(pages.len(), 4);
+
+    let text = pdf.extract_text(&pages).unwrap();
+    assert!(text.starts_with("Staking Rewards 2023\n"));
+    assert!(text.contains("Annual report of staking rewards, valued in EUR."));
+    assert!(text.contains("\nExecution Rewards 2023\n"));
+    assert!(text.contains("\nTotal\n258.42\n71.37\n0.00\n0.99\n328.80\n"));
+    assert!(text.contains("CoinGecko /coins/ethereum/history"));
+}
+
+#[test]
+fn test_wrap() {
+    assert_eq!(
+        wrap("Rewards are listed when received", 16),
+        vec!["Rewards are", "listed when", "received"]
+    );
+    assert_eq!(wrap("", 16), Vec::<String>::new());
+}
@@ -0,0 +1,236 @@
+//! Valuation of reward events in a reporting currency.
+
+use crate::conversion::resolve_prices;
+use crate::tax_profile::TaxProfile;
+use crate::types::RewardEvent;
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use dotenv::dotenv;
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Fiat currency rewards are valued in, selected with `CURRENCY` (defaults to the currency
+/// of the tax profile, EUR for DE).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Currency {
+    #[default]
+    /// Euro.
+    Eur,
+    /// US dollar.
+    Usd,
+    /// Pound sterling.
+    Gbp,
+    /// Swiss franc.
+    Chf,
+}
+
+impl Currency {
+    /// Currency code as used by CoinGecko.
+    pub fn coingecko_id(&self) -> &'static str {
+        match self {
+            Currency::Eur => "eur",
+            Currency::Usd => "usd",
+            Currency::Gbp => "gbp",
+            Currency::Chf => "chf",
+        }
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.coingecko_id().to_uppercase())
+    }
+}
+
+impl FromStr for Currency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "eur" => Ok(Currency::Eur),
+            "usd" => Ok(Currency::Usd),
+            "gbp" => Ok(Currency::Gbp),
+            "chf" => Ok(Currency::Chf),
+            other => Err(format!("Unsupported currency {}", other)),
+        }
+    }
+}
+
+/// Which daily price values a reward, selected with `PRICE_POLICY`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PricePolicy {
+    /// The price at 00:00 UTC of the day the reward was received.
+    #[default]
+    StartOfDay,
+    /// The price at 00:00 UTC of the following day, i.e. the closing price.
+    EndOfDay,
+}
+
+impl PricePolicy {
+    /// Day whose price values a reward received at `date`.
+    pub fn price_date(&self, date: &NaiveDateTime) -> NaiveDate {
+        match self {
+            PricePolicy::StartOfDay => date.date(),
+            PricePolicy::EndOfDay => date.date() + Duration::days(1),
+        }
+    }
+}
+
+impl FromStr for PricePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "start_of_day" => Ok(PricePolicy::StartOfDay),
+            "end_of_day" => Ok(PricePolicy::EndOfDay),
+            other => Err(format!("Unknown price policy {}", other)),
+        }
+    }
+}
+
+/// Values reward events, independently of fetching them. Events can be re-priced under
+/// another currency or policy without querying chain data again.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Pricer {
+    /// Currency rewards are valued in.
+    pub currency: Currency,
+    /// Which daily price values a reward.
+    pub policy: PricePolicy,
+}
+
+impl Pricer {
+    /// Reads `PRICE_POLICY` and the currency of the tax profile, see `TaxProfile::from_env`.
+    pub fn from_env() -> Result<Self, String> {
+        dotenv().ok();
+
+        Ok(Pricer {
+            currency: TaxProfile::from_env()?.currency,
+            policy: dotenv::var("PRICE_POLICY")
+                .ok()
+                .filter(|policy| !policy.is_empty())
+                .map(|policy| policy.parse())
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Prices all events, resolving each distinct asset and date once.
+    pub async fn price_events(&self, events: &mut [RewardEvent]) -> Result<(), Box<dyn Error>> {
+        let requests = self.price_requests(events);
+        println!("Resolving {} {} prices", requests.len(), self.currency);
+
+        let prices = resolve_prices(&requests, self.currency).await?;
+        self.apply_prices(events, &prices)?;
+
+        Ok(())
+    }
+
+    fn price_requests(&self, events: &[RewardEvent]) -> BTreeSet<(String, NaiveDate)> {
+        events
+            .iter()
+            .flat_map(RewardEvent::rewards)
+            .map(|reward| {
+                (
+                    reward.asset.price_id.clone(),
+                    self.policy.price_date(&reward.date),
+                )
+            })
+            .collect()
+    }
+
+    /// Prices all events from resolved `prices` by price id and date, see `price_events`.
+    /// Fails on the first reward without a price.
+    pub fn apply_prices(
+        &self,
+        events: &mut [RewardEvent],
+        prices: &HashMap<(String, NaiveDate), f64>,
+    ) -> Result<(), String> {
+        for reward in events.iter_mut().flat_map(RewardEvent::rewards_mut) {
+            let date = self.policy.price_date(&reward.date);
+            let price = prices
+                .get(&(reward.asset.price_id.clone(), date))
+                .ok_or_else(|| {
+                    format!(
+                        "No {} price of {} on {}",
+                        self.currency, reward.asset.price_id, date
+                    )
+                })?;
+            reward.set_price(*price);
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_reprice_events() {
+    use crate::types::{Asset, Reward};
+    use ethers::types::{Address, U256, U64};
+
+    let priced_in = |timestamp: &str, price_id: &str| {
+        let asset = Asset {
+            price_id: String::from(price_id),
+            decimals: 18,
+        };
+        Reward::new(
+            U64::zero(),
+            String::new(),
+            timestamp.to_string(),
+            U256::exp10(18) * 2,
+            asset,
+            Address::zero(),
+        )
+    };
+    let reward = |timestamp: &str| priced_in(timestamp, "ethereum");
+    // 2023-11-14 22:13:20 and 2023-11-15 12:00:00 UTC
+    let mut events = vec![
+        RewardEvent::Withdrawal {
+            reward: reward("1700000000"),
+        },
+        RewardEvent::ProducedBlock {
+            reward: reward("1700049600"),
+        },
+    ];
+    let day = |d: u32| NaiveDate::from_ymd_opt(2023, 11, d).unwrap();
+    let prices = HashMap::from([
+        ((String::from("ethereum"), day(14)), 1000.0),
+        ((String::from("ethereum"), day(15)), 1100.0),
+        ((String::from("ethereum"), day(16)), 1200.0),
+    ]);
+
+    let pricer = Pricer::default();
+    assert_eq!(pricer.price_requests(&events).len(), 2);
+    pricer.apply_prices(&mut events, &prices).unwrap();
+    assert_eq!(events[0].reward().fiat, 2000.0);
+    assert_eq!(events[1].reward().fiat, 2200.0);
+
+    let pricer = Pricer {
+        currency: Currency::Usd,
+        policy: PricePolicy::EndOfDay,
+    };
+    pricer.apply_prices(&mut events, &prices).unwrap();
+    assert_eq!(events[0].reward().fiat, 2200.0);
+    assert_eq!(events[1].reward().fiat, 2400.0);
+    assert_eq!(
+        pricer.apply_prices(&mut events, &HashMap::new()),
+        Err(String::from("No USD price of ethereum on 2023-11-15"))
+    );
+
+    // Consolidation fees on Gnosis Chain are paid in xDAI, not GNO
+    let mut events = vec![RewardEvent::Consolidation {
+        reward: priced_in("1700000000", "gnosis"),
+        source: String::from("1"),
+        fee: priced_in("1700000000", "xdai"),
+    }];
+    let prices = HashMap::from([
+        ((String::from("gnosis"), day(14)), 100.0),
+        ((String::from("xdai"), day(14)), 1.0),
+    ]);
+    let pricer = Pricer::default();
+    assert_eq!(pricer.price_requests(&events).len(), 2);
+    pricer.apply_prices(&mut events, &prices).unwrap();
+    let RewardEvent::Consolidation { fee, .. } = &events[0] else {
+        unreachable!()
+    };
+    assert_eq!(fee.fiat, 2.0);
+}
@@ -1,157 +1,593 @@
+//! Classification of chain data into reward events, and aggregations over them.
+
 extern crate chrono;
 
-use crate::addresses::{consensus_rewards_address, execution_rewards_address};
-use crate::conversion::coingecko_rate_limiter;
+use crate::addresses::Config;
+use crate::beacon::{beacon_validator, validator_index};
+use crate::conversion::token_price_id;
+use crate::deposits::{deposit_events, split_compounding_withdrawal, split_withdrawal};
 use crate::etherscan::{
     beacon_withdrawal_transactions, etherscan_client, internal_transactions, produced_blocks,
-    transactions,
+    token_transfers, transactions,
+};
+use crate::execution_requests::{
+    consolidation_request_contract, consolidation_requests, resulting_withdrawal,
+    withdrawal_request_contract, withdrawal_requests, ConsolidationRequest,
 };
-use crate::processing::chrono::Datelike;
-use crate::types::{Reward, RewardEvent};
+use crate::liquid_staking::liquid_staking_events;
+use crate::network::Network;
+use crate::rocket_pool::{rocket_pool_events, RocketPool};
+use crate::types::{Principal, Reward, RewardEvent, Token};
 use chrono::NaiveDateTime;
-use ethers::types::U256;
+use ethers::etherscan::account::{ERC20TokenTransferEvent, InternalTransaction, NormalTransaction};
+use ethers::types::{Address, H256, U256, U64};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
 use std::ops::Mul;
-use std::sync::Arc;
 use RewardEvent::Outgoing;
 
-pub async fn process_transactions() -> Vec<RewardEvent> {
-    let client = etherscan_client().unwrap();
-    let execution_addr = execution_rewards_address();
-    let consensus_addr = consensus_rewards_address();
+/// Fetches all chain data of the addresses in `config` and classifies it into unpriced
+/// reward events on `config.network`, see `Pricer::price_events`.
+pub async fn process_transactions(config: &Config) -> Result<Vec<RewardEvent>, Box<dyn Error>> {
+    let network = config.network;
+    let client = etherscan_client(network)?;
+    let execution_addr = config.execution_address;
+    let consensus_addr = config.consensus_address;
 
-    let limiter = Arc::new(coingecko_rate_limiter());
+    let rocket_pool = RocketPool::new(config);
 
     let mut rewards: Vec<RewardEvent> = vec![];
 
     // Produced blocks
-    for block in produced_blocks(&client, execution_addr).await {
+    let blocks = produced_blocks(&client, execution_addr).await?;
+    let produced_block_numbers: HashSet<U64> = blocks
+        .iter()
+        .map(|block| block.block_number.as_number().unwrap())
+        .collect();
+
+    for block in blocks {
         let event = RewardEvent::ProducedBlock {
             reward: Reward::new(
                 block.block_number.as_number().unwrap(),
                 String::from(""),
                 block.time_stamp,
                 U256::from_dec_str(block.block_reward.as_str()).unwrap(),
-                limiter.clone(),
-            )
-            .await,
+                network.native_asset(),
+                execution_addr,
+            ),
         };
         rewards.push(event);
     }
 
+    // Deposits
+    let mut depositors = vec![consensus_addr];
+    if execution_addr != consensus_addr {
+        depositors.push(execution_addr);
+    }
+    depositors.extend(&config.depositors);
+
+    let (deposits, staked) = deposit_events(&client, &depositors, network).await?;
+    let mut principals: HashMap<String, U256> = HashMap::new();
+    for deposit in deposits.iter() {
+        let reward = deposit.reward();
+        *principals.entry(reward.id.clone()).or_default() += reward.amount;
+    }
+    rewards.extend(deposits);
+
     // Transactions
-    for tx in transactions(&client, execution_addr).await {
+    let execution_txs = transactions(&client, execution_addr).await?;
+    let parent_txs: HashMap<H256, &NormalTransaction> = execution_txs
+        .iter()
+        .filter_map(|tx| tx.hash.value().map(|hash| (*hash, tx)))
+        .collect();
+
+    for tx in execution_txs.iter() {
         let event = if tx.to.unwrap() == execution_addr {
             RewardEvent::MevReward {
                 reward: Reward::new(
                     tx.block_number.as_number().unwrap(),
                     format!("{:?}", tx.hash.value().unwrap()),
-                    tx.time_stamp,
+                    tx.time_stamp.clone(),
                     tx.value,
-                    limiter.clone(),
-                )
-                .await,
+                    network.native_asset(),
+                    execution_addr,
+                ),
             }
         } else {
             Outgoing {
                 reward: Reward::new(
                     tx.block_number.as_number().unwrap(),
                     format!("{:?}", tx.hash.value().unwrap()),
-                    tx.time_stamp,
-                    tx.value,
-                    limiter.clone(),
-                )
-                .await,
+                    tx.time_stamp.clone(),
+                    outgoing_value(tx, &staked),
+                    network.native_asset(),
+                    execution_addr,
+                ),
                 fee: tx.gas_used.mul(tx.gas_price.unwrap()),
             }
         };
         rewards.push(event);
     }
 
-    for tx in transactions(&client, consensus_addr).await {
+    let consensus_txs = transactions(&client, consensus_addr).await?;
+    for tx in consensus_txs.iter() {
         if tx.from.value().unwrap().eq(&consensus_addr) {
             let event = Outgoing {
                 reward: Reward::new(
                     tx.block_number.as_number().unwrap(),
                     format!("{:?}", tx.hash.value().unwrap()),
-                    tx.time_stamp,
-                    tx.value,
-                    limiter.clone(),
-                )
-                .await,
+                    tx.time_stamp.clone(),
+                    outgoing_value(tx, &staked),
+                    network.native_asset(),
+                    consensus_addr,
+                ),
                 fee: tx.gas_used.mul(tx.gas_price.unwrap()),
             };
             rewards.push(event);
         }
     }
 
-    // Internal Transactions TODO
-    for tx in internal_transactions(&client, execution_addr).await {
-        let event = RewardEvent::MevRewardInternal {
-            reward: Reward::new(
-                tx.block_number.as_number().unwrap(),
-                format!("{:?}", tx.hash),
-                tx.time_stamp,
-                tx.value,
-                limiter.clone(),
-            )
-            .await,
+    // Internal Transactions
+    let splitters = &config.splitters;
+
+    for tx in internal_transactions(&client, execution_addr).await? {
+        if rocket_pool
+            .as_ref()
+            .is_some_and(|rocket_pool| rocket_pool.handles(&tx.from))
+        {
+            continue;
+        }
+
+        let kind = match classify_internal_transaction(
+            &tx,
+            execution_addr,
+            &parent_txs,
+            &produced_block_numbers,
+            splitters,
+        ) {
+            Some(kind) => kind,
+            None => continue,
+        };
+
+        let reward = Reward::new(
+            tx.block_number.as_number().unwrap(),
+            format!("{:?}", tx.hash),
+            tx.time_stamp,
+            tx.value,
+            network.native_asset(),
+            execution_addr,
+        );
+
+        let event = match kind {
+            InternalTransfer::BuilderPayment => RewardEvent::MevRewardInternal { reward },
+            InternalTransfer::SplitterDistribution => RewardEvent::SplitterDistribution { reward },
+            InternalTransfer::Refund => RewardEvent::Refund { reward },
+            InternalTransfer::Unclassified => RewardEvent::Unclassified { reward },
+            // The gas is paid by the parent transaction, which is already accounted for.
+            InternalTransfer::Outgoing => Outgoing {
+                reward,
+                fee: U256::zero(),
+            },
         };
         rewards.push(event);
     }
 
-    for tx in beacon_withdrawal_transactions(&client, consensus_addr).await {
-        let event = RewardEvent::Withdrawal {
+    // Rocket Pool
+    if let Some(rocket_pool) = &rocket_pool {
+        rewards.extend(rocket_pool_events(&client, rocket_pool, network).await?);
+    }
+
+    // Liquid Staking Tokens
+    for holder in &config.lst_holders {
+        rewards.extend(liquid_staking_events(&client, *holder, network).await?);
+    }
+
+    // Token Transfers
+    let mut token_addrs = vec![execution_addr];
+    if consensus_addr != execution_addr {
+        token_addrs.push(consensus_addr);
+    }
+    let mut senders = config.token_reward_senders.clone();
+    if let Some(rocket_pool) = &rocket_pool {
+        senders.extend(&rocket_pool.distributors);
+    }
+
+    // Builders may pay the fee recipient in tokens, like WETH, within the block
+    let no_blocks = HashSet::new();
+    for address in token_addrs {
+        let proposed = match address == execution_addr {
+            true => &produced_block_numbers,
+            false => &no_blocks,
+        };
+        for tx in token_transfers(&client, address).await? {
+            if !is_token_reward(&tx, address, &senders, proposed) {
+                continue;
+            }
+
+            let price_id =
+                match token_price_id(&tx.contract_address, &config.token_price_ids, network) {
+                    Some(price_id) => price_id,
+                    None => {
+                        println!(
+                            "Skipping transfer of unknown token {} ({:?})",
+                            tx.token_symbol, tx.contract_address
+                        );
+                        continue;
+                    }
+                };
+
+            let token = Token {
+                contract: tx.contract_address,
+                symbol: tx.token_symbol,
+                decimals: tx.token_decimal.parse().unwrap(),
+                price_id,
+            };
+            let event = RewardEvent::TokenReward {
+                reward: Reward::new(
+                    tx.block_number.as_number().unwrap(),
+                    format!("{:?}", tx.hash),
+                    tx.time_stamp,
+                    tx.value,
+                    token.asset(),
+                    address,
+                ),
+                token,
+            };
+            rewards.push(event);
+        }
+    }
+
+    // Withdrawals and consolidations
+    let mut consolidations = consolidation_requests(&consensus_txs)
+        .into_iter()
+        .peekable();
+    let mut compounding: HashMap<u64, bool> = HashMap::new();
+
+    let withdrawals = beacon_withdrawal_transactions(&client, consensus_addr).await?;
+
+    // Withdrawal requests, by the index of the withdrawal they result in
+    let mut requests = vec![];
+    for request in withdrawal_requests(&consensus_txs, network) {
+        let validator_index = validator_index(&request.pubkey, network).await;
+        let withdrawal = validator_index.and_then(|index| {
+            resulting_withdrawal(request.block, index, &withdrawals)
+                .map(|withdrawal| withdrawal.withdrawal_index)
+        });
+        requests.push((request, validator_index, withdrawal));
+    }
+    let requested: HashMap<u64, U256> = requests
+        .iter()
+        .filter_map(|(request, _, withdrawal)| Some(((*withdrawal)?, request.amount)))
+        .collect();
+
+    for tx in withdrawals.iter().cloned() {
+        let block = tx.block_number.as_number().unwrap();
+        while let Some(request) = consolidations.next_if(|request| request.block <= block) {
+            rewards.push(consolidation_event(request, &mut principals, network).await);
+        }
+
+        let validator_index = tx.validator_index.to_string();
+        let amount = network.consensus_to_wei(U256::from_dec_str(tx.amount.as_str()).unwrap());
+        let principal = principals
+            .entry(validator_index.clone())
+            .or_insert(network.validator_principal());
+
+        let is_compounding = match compounding.get(&tx.validator_index) {
+            Some(is_compounding) => *is_compounding,
+            None => {
+                let is_compounding = beacon_validator("head", &validator_index, network)
+                    .await
+                    .is_some_and(|validator| validator.is_compounding());
+                compounding.insert(tx.validator_index, is_compounding);
+                is_compounding
+            }
+        };
+
+        let (returned, reward) = if is_compounding {
+            let slot = network.slot_at(tx.timestamp.parse().unwrap());
+            let balance_after = beacon_validator(&slot.to_string(), &validator_index, network)
+                .await
+                .map(|validator| validator.balance);
+            let requested = requested.get(&tx.withdrawal_index).copied();
+            split_compounding_withdrawal(amount, *principal, balance_after, requested)
+        } else {
+            split_withdrawal(amount, *principal, network)
+        };
+        *principal -= returned;
+
+        if !returned.is_zero() {
+            let event = RewardEvent::PrincipalReturn {
+                reward: Reward::new(
+                    block,
+                    validator_index.clone(),
+                    tx.timestamp.clone(),
+                    returned,
+                    network.staking_asset(),
+                    consensus_addr,
+                ),
+            };
+            rewards.push(event);
+        }
+
+        if !reward.is_zero() {
+            let event = RewardEvent::Withdrawal {
+                reward: Reward::new(
+                    block,
+                    validator_index,
+                    tx.timestamp,
+                    reward,
+                    network.staking_asset(),
+                    consensus_addr,
+                ),
+            };
+            rewards.push(event);
+        }
+    }
+
+    for request in consolidations {
+        rewards.push(consolidation_event(request, &mut principals, network).await);
+    }
+
+    // Withdrawal requests
+    for (request, validator_index, withdrawal) in requests {
+        let event = RewardEvent::WithdrawalRequest {
             reward: Reward::new(
-                tx.block_number.as_number().unwrap(),
-                tx.validator_index.to_string(),
-                tx.timestamp,
-                U256::exp10(9) * U256::from_dec_str(tx.amount.as_str()).unwrap(),
-                limiter.clone(),
-            )
-            .await,
+                request.block,
+                validator_index
+                    .map(|index| index.to_string())
+                    .unwrap_or(request.pubkey),
+                request.timestamp,
+                request.fee,
+                network.native_asset(),
+                request.from,
+            ),
+            amount: request.amount,
+            withdrawal,
         };
         rewards.push(event);
     }
 
-    rewards
+    Ok(rewards)
 }
 
-pub fn range_filter(date: &NaiveDateTime, year: i32) -> bool {
-    date.year() == year
+/// Moves the principal of the consolidation source to its target.
+async fn consolidation_event(
+    request: ConsolidationRequest,
+    principals: &mut HashMap<String, U256>,
+    network: Network,
+) -> RewardEvent {
+    let source = match validator_index(&request.source, network).await {
+        Some(index) => index.to_string(),
+        None => request.source,
+    };
+    let target = match validator_index(&request.target, network).await {
+        Some(index) => index.to_string(),
+        None => request.target,
+    };
+
+    let moved = move_principal(&source, &target, principals, network);
+
+    RewardEvent::Consolidation {
+        reward: Reward::new(
+            request.block,
+            target.clone(),
+            request.timestamp.clone(),
+            moved,
+            network.staking_asset(),
+            request.from,
+        ),
+        source,
+        fee: Reward::new(
+            request.block,
+            target,
+            request.timestamp,
+            request.fee,
+            network.native_asset(),
+            request.from,
+        ),
+    }
 }
 
-pub fn current_balance(events: &Vec<RewardEvent>) -> U256 {
-    let mut sum = U256::from(0);
+/// Moves the principal of `source` to `target` and returns the amount moved. Validators
+/// not seen before hold the principal of a single validator. A consolidation of a validator
+/// into itself switches its withdrawal credentials to compounding and moves nothing.
+fn move_principal(
+    source: &str,
+    target: &str,
+    principals: &mut HashMap<String, U256>,
+    network: Network,
+) -> U256 {
+    let principal = network.validator_principal();
+    if source == target {
+        principals.entry(target.to_string()).or_insert(principal);
+        return U256::zero();
+    }
 
-    for e in events {
-        match e {
-            RewardEvent::ProducedBlock { reward, .. } => sum += reward.amount,
-            RewardEvent::Withdrawal { reward, .. } => sum += reward.amount,
-            RewardEvent::MevReward { reward, .. } => sum += reward.amount,
-            RewardEvent::MevRewardInternal { reward, .. } => sum += reward.amount,
-            Outgoing { reward, fee: gas } => sum -= reward.amount + gas,
+    let moved = principals.remove(source).unwrap_or(principal);
+    *principals.entry(target.to_string()).or_insert(principal) += moved;
+    moved
+}
+
+/// Whether `tx` pays a token reward to `address`. Only transfers from reward `senders`, and
+/// builder payments within the `proposed` blocks `address` received the fees of, are income,
+/// not self-transfers, swaps or withdrawals from exchanges.
+fn is_token_reward(
+    tx: &ERC20TokenTransferEvent,
+    address: Address,
+    senders: &[Address],
+    proposed: &HashSet<U64>,
+) -> bool {
+    tx.to == Some(address)
+        && tx.from != address
+        && (senders.contains(&tx.from)
+            || tx
+                .block_number
+                .as_number()
+                .is_some_and(|block| proposed.contains(&block)))
+}
+
+/// Value leaving the address with `tx`. The `staked` value of deposits is principal, which
+/// is tracked separately as `RewardEvent::Deposit`. Request fees are reported with
+/// `RewardEvent::Consolidation` and `RewardEvent::WithdrawalRequest`.
+fn outgoing_value(tx: &NormalTransaction, staked: &HashMap<H256, U256>) -> U256 {
+    if tx.to == Some(consolidation_request_contract())
+        || tx.to == Some(withdrawal_request_contract())
+    {
+        return U256::zero();
+    }
+
+    let staked = tx
+        .hash
+        .value()
+        .and_then(|hash| staked.get(hash))
+        .copied()
+        .unwrap_or_default();
+    tx.value.saturating_sub(staked)
+}
+
+/// Kind of value transfer an internal transaction represents for the execution rewards address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InternalTransfer {
+    /// Payment from a block builder to the fee recipient within a block we proposed.
+    BuilderPayment,
+    /// Payout from a fee recipient splitter contract.
+    SplitterDistribution,
+    /// Value returned by a contract called from one of our own transactions.
+    Refund,
+    /// Value sent out of the address by a contract call.
+    Outgoing,
+    /// Value received from a contract that is neither of the above, to be reviewed.
+    Unclassified,
+}
+
+/// Classifies an internal transaction of `address`.
+///
+/// Returns `None` for transfers that carry no value, failed, don't touch `address`,
+/// or duplicate a normal transfer already recorded for the same transaction.
+pub fn classify_internal_transaction(
+    tx: &InternalTransaction,
+    address: Address,
+    parent_txs: &HashMap<H256, &NormalTransaction>,
+    produced_blocks: &HashSet<U64>,
+    splitters: &[Address],
+) -> Option<InternalTransfer> {
+    if tx.is_error != "0" || tx.value.is_zero() {
+        return None;
+    }
+
+    let to = tx.to.value().copied();
+    let incoming = to == Some(address);
+    let outgoing = tx.from == address;
+    if incoming == outgoing {
+        return None;
+    }
+
+    let parent = parent_txs.get(&tx.hash);
+    if let Some(parent) = parent {
+        if parent.from.value() == Some(&tx.from) && parent.to == to && parent.value == tx.value {
+            return None;
         }
     }
 
+    if outgoing {
+        return Some(InternalTransfer::Outgoing);
+    }
+
+    if parent.is_some_and(|parent| parent.from.value() == Some(&address)) {
+        Some(InternalTransfer::Refund)
+    } else if splitters.contains(&tx.from) {
+        Some(InternalTransfer::SplitterDistribution)
+    } else if produced_blocks.contains(&tx.block_number.as_number().unwrap()) {
+        Some(InternalTransfer::BuilderPayment)
+    } else {
+        println!(
+            "Unclassified internal transaction {:?} from {:?}, not counted as income",
+            tx.hash, tx.from
+        );
+        Some(InternalTransfer::Unclassified)
+    }
+}
+
+/// Balance of the reward addresses in wei implied by `events`.
+pub fn current_balance(events: &[RewardEvent]) -> U256 {
+    events.iter().fold(U256::zero(), balance_after)
+}
+
+/// Balance of the reward addresses in wei after each of the sorted `events`.
+pub fn balance_history(events: &[RewardEvent]) -> Vec<(NaiveDateTime, U256)> {
+    let mut sum = U256::zero();
+    events
+        .iter()
+        .map(|event| {
+            sum = balance_after(sum, event);
+            (event.reward().date, sum)
+        })
+        .collect()
+}
+
+fn balance_after(mut sum: U256, e: &RewardEvent) -> U256 {
+    match e {
+        RewardEvent::ProducedBlock { reward, .. } => sum += reward.amount,
+        RewardEvent::Withdrawal { reward, .. } => sum += reward.amount,
+        RewardEvent::MevReward { reward, .. } => sum += reward.amount,
+        RewardEvent::MevRewardInternal { reward, .. } => sum += reward.amount,
+        RewardEvent::SplitterDistribution { reward, .. } => sum += reward.amount,
+        RewardEvent::Refund { reward, .. } => sum += reward.amount,
+        RewardEvent::MinipoolDistribution { reward, .. } => sum += reward.amount,
+        RewardEvent::SmoothingPoolClaim { reward, .. } => sum += reward.amount,
+        RewardEvent::BondReturn { reward, .. } => sum += reward.amount,
+        RewardEvent::LiquidStakingReward { .. } => {}
+        RewardEvent::Deposit { reward, .. } => sum = pay(sum, reward.amount, reward),
+        RewardEvent::PrincipalReturn { reward } => sum += reward.amount,
+        RewardEvent::Consolidation { fee, .. } => sum = pay(sum, fee.amount, fee),
+        RewardEvent::WithdrawalRequest { reward, .. } => sum = pay(sum, reward.amount, reward),
+        RewardEvent::TokenReward { .. } => {}
+        RewardEvent::Unclassified { reward } => sum += reward.amount,
+        Outgoing { reward, fee: gas } => sum = pay(sum, reward.amount + gas, reward),
+    }
+
     sum
 }
 
-pub fn total_earnings(events: &Vec<RewardEvent>) -> f64 {
-    let mut sum = 0.0;
+/// Deducts a payment of `amount` from `balance`. Deposits and other payments may be funded
+/// from addresses other than the reward addresses, so the balance implied by the events
+/// alone can run out. It's then reported as zero, with a warning.
+fn pay(balance: U256, amount: U256, reward: &Reward) -> U256 {
+    balance.checked_sub(amount).unwrap_or_else(|| {
+        println!(
+            "Payment {} of {} exceeds the balance of {}, assuming it was funded elsewhere",
+            reward.id, amount, balance
+        );
+        U256::zero()
+    })
+}
+
+/// Principal deposited to and returned from each validator, keyed by validator index
+/// (or pubkey if the index could not be resolved).
+pub fn principal_by_validator(events: &[RewardEvent]) -> BTreeMap<String, Principal> {
+    let mut principals: BTreeMap<String, Principal> = BTreeMap::new();
 
     for e in events {
         match e {
-            RewardEvent::ProducedBlock { reward, .. } => sum += reward.fiat,
-            RewardEvent::Withdrawal { reward, .. } => sum += reward.fiat,
-            RewardEvent::MevReward { reward, .. } => sum += reward.fiat,
-            RewardEvent::MevRewardInternal { reward, .. } => sum += reward.fiat,
-            Outgoing { .. } => {}
+            RewardEvent::Deposit { reward, .. } => {
+                let principal = principals.entry(reward.id.clone()).or_default();
+                principal.deposited += reward.amount;
+                principal.deposited_fiat += reward.fiat;
+            }
+            RewardEvent::PrincipalReturn { reward } => {
+                let principal = principals.entry(reward.id.clone()).or_default();
+                principal.returned += reward.amount;
+                principal.returned_fiat += reward.fiat;
+            }
+            _ => {}
         }
     }
-    sum
+
+    principals
 }
 
-pub fn unliquidated(events: &Vec<RewardEvent>) -> f64 {
+/// Fiat value of the rewards received, less the value sent out.
+pub fn unliquidated(events: &[RewardEvent]) -> f64 {
     let mut sum = 0.0;
 
     for e in events {
@@ -160,8 +596,345 @@ pub fn unliquidated(events: &Vec<RewardEvent>) -> f64 {
             RewardEvent::Withdrawal { reward, .. } => sum += reward.fiat,
             RewardEvent::MevReward { reward, .. } => sum += reward.fiat,
             RewardEvent::MevRewardInternal { reward, .. } => sum += reward.fiat,
+            RewardEvent::SplitterDistribution { reward, .. } => sum += reward.fiat,
+            RewardEvent::Refund { reward, .. } => sum += reward.fiat,
+            RewardEvent::MinipoolDistribution { reward, .. } => sum += reward.fiat,
+            RewardEvent::SmoothingPoolClaim { reward, .. } => sum += reward.fiat,
+            RewardEvent::BondReturn { reward, .. } => sum += reward.fiat,
+            RewardEvent::LiquidStakingReward { reward, .. } => sum += reward.fiat,
+            RewardEvent::Deposit { .. } => {}
+            RewardEvent::PrincipalReturn { .. } => {}
+            RewardEvent::Consolidation { .. } => {}
+            RewardEvent::WithdrawalRequest { .. } => {}
+            RewardEvent::TokenReward { reward, .. } => sum += reward.fiat,
+            RewardEvent::Unclassified { reward } => sum += reward.fiat,
             Outgoing { reward, .. } => sum -= reward.fiat,
         }
     }
     sum
 }
+
+#[cfg(test)]
+fn internal_tx(
+    from: Address,
+    to: Address,
+    value: u64,
+    hash: H256,
+    block: u64,
+) -> InternalTransaction {
+    serde_json::from_value(serde_json::json!({
+        "blockNumber": block.to_string(),
+        "timeStamp": "1700000000",
+        "hash": format!("{:?}", hash),
+        "from": format!("{:?}", from),
+        "to": format!("{:?}", to),
+        "value": value.to_string(),
+        "contractAddress": "",
+        "input": "",
+        "type": "call",
+        "gas": "0",
+        "gasUsed": "0",
+        "traceId": "0",
+        "isError": "0",
+        "errCode": "",
+    }))
+    .unwrap()
+}
+
+#[cfg(test)]
+fn normal_tx(from: Address, to: Address, value: u64, hash: H256) -> NormalTransaction {
+    serde_json::from_value(serde_json::json!({
+        "isError": "0",
+        "blockNumber": "100",
+        "timeStamp": "1700000000",
+        "hash": format!("{:?}", hash),
+        "nonce": "1",
+        "blockHash": "",
+        "transactionIndex": "0",
+        "from": format!("{:?}", from),
+        "to": format!("{:?}", to),
+        "value": value.to_string(),
+        "gas": "21000",
+        "gasPrice": "1",
+        "txreceipt_status": "1",
+        "input": "0x",
+        "contractAddress": "",
+        "gasUsed": "21000",
+        "cumulativeGasUsed": "21000",
+        "confirmations": "1",
+        "methodId": "0x",
+        "functionName": "",
+    }))
+    .unwrap()
+}
+
+#[test]
+fn test_classify_internal_transaction() {
+    let own = Address::from_low_u64_be(1);
+    let builder = Address::from_low_u64_be(2);
+    let splitter = Address::from_low_u64_be(3);
+    let contract = Address::from_low_u64_be(4);
+
+    let own_tx = normal_tx(own, contract, 10, H256::from_low_u64_be(1));
+    let parent_txs = HashMap::from([(H256::from_low_u64_be(1), &own_tx)]);
+    let produced = HashSet::from([U64::from(100)]);
+    let splitters = vec![splitter];
+
+    let classify = |tx: &InternalTransaction| {
+        classify_internal_transaction(tx, own, &parent_txs, &produced, &splitters)
+    };
+
+    assert_eq!(
+        classify(&internal_tx(builder, own, 5, H256::from_low_u64_be(2), 100)),
+        Some(InternalTransfer::BuilderPayment)
+    );
+    assert_eq!(
+        classify(&internal_tx(
+            contract,
+            own,
+            5,
+            H256::from_low_u64_be(1),
+            100
+        )),
+        Some(InternalTransfer::Refund)
+    );
+    assert_eq!(
+        classify(&internal_tx(
+            splitter,
+            own,
+            5,
+            H256::from_low_u64_be(3),
+            200
+        )),
+        Some(InternalTransfer::SplitterDistribution)
+    );
+    assert_eq!(
+        classify(&internal_tx(
+            own,
+            contract,
+            5,
+            H256::from_low_u64_be(4),
+            200
+        )),
+        Some(InternalTransfer::Outgoing)
+    );
+    // Duplicate of the normal transfer in the same transaction
+    assert_eq!(
+        classify(&internal_tx(
+            own,
+            contract,
+            10,
+            H256::from_low_u64_be(1),
+            100
+        )),
+        None
+    );
+    // Received from a contract in a block we didn't propose
+    assert_eq!(
+        classify(&internal_tx(
+            contract,
+            own,
+            5,
+            H256::from_low_u64_be(6),
+            300
+        )),
+        Some(InternalTransfer::Unclassified)
+    );
+    // Doesn't touch the address
+    assert_eq!(
+        classify(&internal_tx(
+            builder,
+            contract,
+            5,
+            H256::from_low_u64_be(5),
+            100
+        )),
+        None
+    );
+}
+
+#[test]
+fn test_outgoing_value() {
+    let own = Address::from_low_u64_be(1);
+    let weth: Address = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"
+        .parse()
+        .unwrap();
+
+    // Wrapping ETH calls `deposit()` without staking anything
+    let mut wrap = normal_tx(own, weth, 10, H256::from_low_u64_be(1));
+    wrap.function_name = Some(String::from("deposit()"));
+    assert!(crate::deposits::is_deposit_candidate(
+        &wrap,
+        Network::Mainnet
+    ));
+    assert_eq!(outgoing_value(&wrap, &HashMap::new()), U256::from(10));
+
+    let deposit = normal_tx(
+        own,
+        Network::Mainnet.deposit_contract(),
+        10,
+        H256::from_low_u64_be(2),
+    );
+    let staked = HashMap::from([(H256::from_low_u64_be(2), U256::from(10))]);
+    assert_eq!(outgoing_value(&deposit, &staked), U256::zero());
+}
+
+#[test]
+fn test_is_token_reward() {
+    let own = Address::from_low_u64_be(1);
+    let distributor = Address::from_low_u64_be(2);
+    let exchange = Address::from_low_u64_be(3);
+    let transfer = |from: Address, to: Address| -> ERC20TokenTransferEvent {
+        serde_json::from_value(serde_json::json!({
+            "blockNumber": "100",
+            "timeStamp": "1700000000",
+            "hash": format!("{:?}", H256::zero()),
+            "nonce": "1",
+            "blockHash": format!("{:?}", H256::zero()),
+            "from": format!("{:?}", from),
+            "contractAddress": "0xd33526068d116ce69f19a9ee46f0bd304f21a51f",
+            "to": format!("{:?}", to),
+            "value": "1000",
+            "tokenName": "Rocket Pool Protocol",
+            "tokenSymbol": "RPL",
+            "tokenDecimal": "18",
+            "transactionIndex": "0",
+            "gas": "21000",
+            "gasPrice": "1",
+            "gasUsed": "21000",
+            "cumulativeGasUsed": "21000",
+            "input": "deprecated",
+            "confirmations": "1",
+        }))
+        .unwrap()
+    };
+
+    assert!(is_token_reward(
+        &transfer(distributor, own),
+        own,
+        &[distributor],
+        &HashSet::new()
+    ));
+    assert!(!is_token_reward(
+        &transfer(exchange, own),
+        own,
+        &[distributor],
+        &HashSet::new()
+    ));
+    assert!(!is_token_reward(
+        &transfer(own, own),
+        own,
+        &[distributor],
+        &HashSet::new()
+    ));
+    assert!(!is_token_reward(
+        &transfer(distributor, exchange),
+        own,
+        &[distributor],
+        &HashSet::new()
+    ));
+
+    // Builder payment in WETH within a proposed block
+    let proposed = HashSet::from([U64::from(100)]);
+    assert!(is_token_reward(
+        &transfer(exchange, own),
+        own,
+        &[distributor],
+        &proposed
+    ));
+    assert!(!is_token_reward(
+        &transfer(own, own),
+        own,
+        &[distributor],
+        &proposed
+    ));
+}
+
+#[test]
+fn test_principal_by_validator() {
+    let reward = |id: &str, eth: u64, fiat: f64| {
+        Reward::priced(
+            U64::zero(),
+            id.to_string(),
+            NaiveDateTime::default(),
+            U256::exp10(18) * eth,
+            Network::Mainnet.staking_asset(),
+            Address::zero(),
+            fiat / eth as f64,
+        )
+    };
+    let events = vec![
+        RewardEvent::Deposit {
+            reward: reward("1", 32, 32000.0),
+            pubkey: String::from("0x01"),
+        },
+        RewardEvent::PrincipalReturn {
+            reward: reward("1", 32, 64000.0),
+        },
+        RewardEvent::Withdrawal {
+            reward: reward("1", 1, 2000.0),
+        },
+    ];
+
+    let principals = principal_by_validator(&events);
+    assert_eq!(
+        principals["1"],
+        Principal {
+            deposited: U256::exp10(18) * 32,
+            deposited_fiat: 32000.0,
+            returned: U256::exp10(18) * 32,
+            returned_fiat: 64000.0,
+        }
+    );
+}
+
+#[test]
+fn test_current_balance() {
+    let reward = |eth: u64| {
+        Reward::priced(
+            U64::zero(),
+            String::from("1"),
+            NaiveDateTime::default(),
+            U256::exp10(18) * eth,
+            Network::Mainnet.staking_asset(),
+            Address::zero(),
+            0.0,
+        )
+    };
+    let mut events = vec![
+        RewardEvent::Withdrawal { reward: reward(1) },
+        RewardEvent::Outgoing {
+            reward: reward(1),
+            fee: U256::zero(),
+        },
+    ];
+    assert_eq!(current_balance(&events), U256::zero());
+
+    // A deposit funded from elsewhere doesn't take the balance below zero
+    events.push(RewardEvent::Deposit {
+        reward: reward(32),
+        pubkey: String::from("0x01"),
+    });
+    events.push(RewardEvent::Withdrawal { reward: reward(2) });
+    assert_eq!(current_balance(&events), U256::exp10(18) * 2);
+}
+
+#[test]
+fn test_move_principal() {
+    let principal = Network::Mainnet.validator_principal();
+    let mut principals = HashMap::from([(String::from("1"), principal)]);
+
+    // Switching to compounding credentials keeps the principal
+    assert_eq!(
+        move_principal("1", "1", &mut principals, Network::Mainnet),
+        U256::zero()
+    );
+    assert_eq!(principals["1"], principal);
+
+    assert_eq!(
+        move_principal("2", "1", &mut principals, Network::Mainnet),
+        principal
+    );
+    assert_eq!(principals["1"], principal * 2);
+    assert!(!principals.contains_key("2"));
+}
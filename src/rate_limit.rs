@@ -0,0 +1,208 @@
+//! Per-provider token bucket rate limits.
+
+use chrono::Utc;
+use dotenv::dotenv;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const USAGE_FILE_PATH: &str = "api_usage.json";
+
+/// Token bucket refilled at `rate` tokens per second, up to `capacity`.
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    tokens: f64,
+    rate: f64,
+    /// Configured rate, which `rate` recovers to after being throttled.
+    max_rate: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(calls_per_second: f64, now: Instant) -> Self {
+        let capacity = calls_per_second.max(1.0);
+        Bucket {
+            capacity,
+            tokens: capacity,
+            rate: calls_per_second,
+            max_rate: calls_per_second,
+            updated: now,
+        }
+    }
+
+    /// Takes a token, or returns how long to wait until one is available.
+    fn take(&mut self, now: Instant) -> Result<(), Duration> {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+        }
+    }
+
+    /// Halves the rate and drains the bucket, down to a sixteenth of the configured rate.
+    fn throttle(&mut self) {
+        self.rate = (self.rate / 2.0).max(self.max_rate / 16.0);
+        self.tokens = 0.0;
+    }
+
+    /// Recovers a twentieth of the configured rate per successful call.
+    fn recover(&mut self) {
+        self.rate = (self.rate + self.max_rate / 20.0).min(self.max_rate);
+    }
+}
+
+/// Calls made per provider and month, persisted so budgets hold across runs.
+type Usage = HashMap<String, HashMap<String, u64>>;
+
+fn read_usage() -> Usage {
+    fs::read_to_string(USAGE_FILE_PATH)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Per-provider rate limiter, allowing concurrent calls within a token bucket and a
+/// monthly call budget. The rate adapts downward whenever the provider throttles us.
+#[derive(Debug)]
+pub struct RateLimiter {
+    name: &'static str,
+    bucket: Mutex<Bucket>,
+    calls_per_month: Option<u64>,
+}
+
+impl RateLimiter {
+    pub fn new(name: &'static str, calls_per_second: f64, calls_per_month: Option<u64>) -> Self {
+        RateLimiter {
+            name,
+            bucket: Mutex::new(Bucket::new(calls_per_second, Instant::now())),
+            calls_per_month,
+        }
+    }
+
+    /// Reads the limits from `<PREFIX>_CALLS_PER_SECOND` and `<PREFIX>_CALLS_PER_MONTH`,
+    /// falling back to `calls_per_second` and no monthly budget.
+    pub fn from_env(name: &'static str, prefix: &str, calls_per_second: f64) -> Self {
+        dotenv().ok();
+
+        let calls_per_second = dotenv::var(format!("{}_CALLS_PER_SECOND", prefix))
+            .ok()
+            .and_then(|rate| rate.parse::<f64>().ok())
+            .filter(|rate| *rate > 0.0)
+            .unwrap_or(calls_per_second);
+        let calls_per_month = dotenv::var(format!("{}_CALLS_PER_MONTH", prefix))
+            .ok()
+            .and_then(|budget| budget.parse::<u64>().ok());
+
+        println!(
+            "{} rate limit: {} calls / s{}",
+            name,
+            calls_per_second,
+            calls_per_month
+                .map(|budget| format!(", {} calls / month", budget))
+                .unwrap_or_default()
+        );
+
+        Self::new(name, calls_per_second, calls_per_month)
+    }
+
+    /// Waits for a token. Fails if the monthly budget is used up.
+    pub async fn acquire(&self) -> Result<(), String> {
+        loop {
+            let wait = self.bucket.lock().unwrap().take(Instant::now());
+            match wait {
+                Ok(()) => break,
+                Err(delay) => tokio::time::sleep(delay).await,
+            }
+        }
+
+        match self.calls_per_month {
+            Some(budget) => self.count_call(budget),
+            None => Ok(()),
+        }
+    }
+
+    fn count_call(&self, budget: u64) -> Result<(), String> {
+        // Held while updating the file, so concurrent calls don't lose counts
+        let _bucket = self.bucket.lock().unwrap();
+
+        let month = Utc::now().format("%Y-%m").to_string();
+        let mut usage = read_usage();
+        let calls = usage
+            .entry(self.name.to_string())
+            .or_default()
+            .entry(month.clone())
+            .or_default();
+        if *calls >= budget {
+            return Err(format!(
+                "{} budget of {} calls in {} used up",
+                self.name, budget, month
+            ));
+        }
+        *calls += 1;
+
+        let serialized = serde_json::to_string_pretty(&usage).map_err(|e| e.to_string())?;
+        fs::write(USAGE_FILE_PATH, serialized).map_err(|e| e.to_string())
+    }
+
+    /// Current rate in calls per second.
+    #[cfg(test)]
+    pub fn rate(&self) -> f64 {
+        self.bucket.lock().unwrap().rate
+    }
+
+    /// The provider answered with 429, or reported its rate limit in the response body.
+    pub fn throttled(&self) {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.throttle();
+        println!(
+            "{} throttled, slowing to {:.3} calls / s",
+            self.name, bucket.rate
+        );
+    }
+
+    pub fn succeeded(&self) {
+        self.bucket.lock().unwrap().recover();
+    }
+}
+
+#[test]
+fn test_bucket() {
+    let start = Instant::now();
+    let mut bucket = Bucket::new(2.0, start);
+
+    // The full capacity is available at once for concurrent calls
+    assert_eq!(bucket.take(start), Ok(()));
+    assert_eq!(bucket.take(start), Ok(()));
+    assert_eq!(bucket.take(start), Err(Duration::from_millis(500)));
+    assert_eq!(bucket.take(start + Duration::from_millis(500)), Ok(()));
+
+    // Slow providers still allow a single call at a time
+    let mut bucket = Bucket::new(0.5, start);
+    assert_eq!(bucket.take(start), Ok(()));
+    assert_eq!(bucket.take(start), Err(Duration::from_secs(2)));
+}
+
+#[test]
+fn test_bucket_adapts_to_throttling() {
+    let mut bucket = Bucket::new(4.0, Instant::now());
+
+    bucket.throttle();
+    assert_eq!(bucket.rate, 2.0);
+    assert_eq!(bucket.tokens, 0.0);
+    for _ in 0..10 {
+        bucket.throttle();
+    }
+    assert_eq!(bucket.rate, 0.25);
+
+    for _ in 0..100 {
+        bucket.recover();
+    }
+    assert_eq!(bucket.rate, 4.0);
+}
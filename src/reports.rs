@@ -0,0 +1,838 @@
+//! Yearly reports of the rewards, fees and staked principal, and the formats they are
+//! written in.
+//!
+//! Events are first laid out as a [`Report`] of sections, rows and totals, which each
+//! [`ReportWriter`] then renders, so every format lists the same figures.
+
+use crate::addresses::Config;
+use crate::anlage_so::AnlageSoReport;
+use crate::blockpit_report::BlockpitReport;
+use crate::cointracking_report::CoinTrackingReport;
+use crate::csv_report::CsvReport;
+use crate::datev_report::DatevReport;
+use crate::html_report::HtmlReport;
+use crate::json_report::JsonReport;
+use crate::koinly_report::KoinlyReport;
+use crate::ledger_report::{BeancountReport, HledgerReport};
+use crate::liquid_staking::Accrual;
+use crate::lots::{disposals, movements, Disposal, Movement};
+use crate::markdown_report::MarkdownReport;
+use crate::network::Network;
+use crate::pdf_report::PdfReport;
+use crate::pricing::{PricePolicy, Pricer};
+use crate::processing::{balance_history, principal_by_validator};
+use crate::tax_profile::{IncomeRecognition, TaxProfile};
+use crate::types::{Reward, RewardEvent, Token};
+use crate::uk_report::UkReport;
+use crate::us_report::UsReport;
+use crate::xlsx_report::XlsxReport;
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use ethers::types::{U256, U64};
+use ethers::utils::{format_ether, format_units};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Yearly report an entry is listed on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    /// Rewards for proposing blocks, booked as mining income by tax tools.
+    Execution,
+    /// Rewards for attesting, booked as staking income.
+    Consensus,
+    /// Transaction and consolidation fees.
+    Fees,
+    /// Token rewards, such as Rocket Pool RPL.
+    Tokens,
+}
+
+/// One income or fee of the year, as listed on the reports and exported to tax tools.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// Category the entry is totalled in.
+    pub category: Category,
+    /// When the entry was received or paid.
+    pub date: NaiveDateTime,
+    /// Block the entry was included in.
+    pub block: U64,
+    /// Kind of the event, e.g. `Withdrawal`.
+    pub kind: String,
+    /// Transaction hash or validator index.
+    pub id: String,
+    /// Exact amount in units of the asset, e.g. ETH rather than wei.
+    pub amount: String,
+    /// Symbol of the asset.
+    pub symbol: String,
+    /// Reward address receiving the entry or paying the fee.
+    pub address: String,
+    /// Price per unit in the reporting currency.
+    pub price: f64,
+    /// Value in the reporting currency.
+    pub fiat: f64,
+}
+
+impl Entry {
+    fn reward(network: Network, category: Category, reward: &Reward, kind: &str) -> Self {
+        let symbol = match reward.asset == network.native_asset() {
+            true => network.native_symbol(),
+            false => network.staking_symbol(),
+        };
+        Entry {
+            category,
+            date: reward.date,
+            block: reward.block,
+            kind: kind.to_string(),
+            id: reward.id.clone(),
+            amount: format_ether(reward.amount),
+            symbol: symbol.to_string(),
+            address: format!("{:?}", reward.address),
+            price: reward.price,
+            fiat: reward.fiat,
+        }
+    }
+
+    fn fee(network: Network, reward: &Reward, fee: U256, kind: &str) -> Self {
+        let amount = format_ether(fee);
+        Entry {
+            fiat: amount.parse::<f64>().unwrap() * reward.price,
+            amount,
+            ..Self::reward(network, Category::Fees, reward, kind)
+        }
+    }
+
+    fn token(network: Network, reward: &Reward, token: &Token) -> Self {
+        Entry {
+            amount: format_units(reward.amount, token.decimals).unwrap(),
+            symbol: token.symbol.clone(),
+            ..Self::reward(network, Category::Tokens, reward, &token.symbol)
+        }
+    }
+
+    /// Hash of the transaction paying the entry. Withdrawals and blocks are not paid by
+    /// one and are identified by validator or block instead.
+    pub fn tx_hash(&self) -> Option<&str> {
+        (self.id.starts_with("0x") && self.id.len() == 66).then_some(self.id.as_str())
+    }
+
+    fn cells(&self) -> Vec<Cell> {
+        vec![
+            Cell::Date(self.date),
+            Cell::Integer(self.block.as_u64()),
+            Cell::Text(self.kind.clone()),
+            Cell::Text(self.id.clone()),
+            Cell::Amount(self.amount.parse().unwrap()),
+            Cell::Fiat(self.price),
+            Cell::Fiat(self.fiat),
+        ]
+    }
+}
+
+/// Entries of the sorted `events` on `network` in the fiscal year `year`, in the order they
+/// are listed.
+fn entries(
+    events: &[RewardEvent],
+    year: i32,
+    profile: &TaxProfile,
+    network: Network,
+) -> Vec<Entry> {
+    events
+        .iter()
+        .filter(|event| profile.in_fiscal_year(&event.reward().date, year))
+        .filter_map(|event| match event {
+            RewardEvent::Withdrawal { reward } => Some(Entry::reward(
+                network,
+                Category::Consensus,
+                reward,
+                "Withdrawal",
+            )),
+            RewardEvent::ProducedBlock { reward } => {
+                Some(Entry::reward(network, Category::Execution, reward, "Block"))
+            }
+            RewardEvent::MevReward { reward } => Some(Entry::reward(
+                network,
+                Category::Execution,
+                reward,
+                "MevReward",
+            )),
+            RewardEvent::MevRewardInternal { reward } => Some(Entry::reward(
+                network,
+                Category::Execution,
+                reward,
+                "MevRewardInternal",
+            )),
+            RewardEvent::SplitterDistribution { reward } => Some(Entry::reward(
+                network,
+                Category::Execution,
+                reward,
+                "SplitterDistribution",
+            )),
+            RewardEvent::Refund { .. } => None,
+            RewardEvent::MinipoolDistribution { reward } => Some(Entry::reward(
+                network,
+                Category::Consensus,
+                reward,
+                "MinipoolDistribution",
+            )),
+            RewardEvent::SmoothingPoolClaim { reward } => Some(Entry::reward(
+                network,
+                Category::Execution,
+                reward,
+                "SmoothingPoolClaim",
+            )),
+            RewardEvent::BondReturn { .. } => None,
+            RewardEvent::Deposit { .. } => None,
+            RewardEvent::PrincipalReturn { .. } => None,
+            RewardEvent::Consolidation { fee, .. } => {
+                Some(Entry::fee(network, fee, fee.amount, "ConsolidationFee"))
+            }
+            RewardEvent::WithdrawalRequest { reward, .. } => Some(Entry::fee(
+                network,
+                reward,
+                reward.amount,
+                "WithdrawalRequestFee",
+            )),
+            RewardEvent::LiquidStakingReward { reward, symbol } => Some(Entry::reward(
+                network,
+                Category::Consensus,
+                reward,
+                &format!("{} {}", symbol, Accrual::of(symbol).label()),
+            )),
+            RewardEvent::TokenReward { reward, token } => {
+                Some(Entry::token(network, reward, token))
+            }
+            RewardEvent::Unclassified { .. } => None,
+            RewardEvent::Outgoing { reward, fee } => {
+                (!fee.is_zero()).then(|| Entry::fee(network, reward, *fee, "Fee"))
+            }
+        })
+        .collect()
+}
+
+/// Typed value of a report cell.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    /// Date and time in UTC.
+    Date(NaiveDateTime),
+    /// Whole number, like a block or validator index.
+    Integer(u64),
+    /// Free text.
+    Text(String),
+    /// Amount of an asset, listed with 8 decimals.
+    Amount(f64),
+    /// Price or value in the reporting currency.
+    Fiat(f64),
+}
+
+impl Cell {
+    /// The number held by the cell, `None` for dates and text.
+    pub fn value(&self) -> Option<f64> {
+        match self {
+            Cell::Amount(value) | Cell::Fiat(value) => Some(*value),
+            Cell::Integer(value) => Some(*value as f64),
+            Cell::Date(_) | Cell::Text(_) => None,
+        }
+    }
+}
+
+/// Values as shown in documents, amounts with 8 and fiat values with 2 decimals.
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cell::Date(date) => write!(f, "{}", date),
+            Cell::Integer(value) => write!(f, "{}", value),
+            Cell::Text(text) => write!(f, "{}", text),
+            Cell::Amount(amount) => write!(f, "{:.8}", amount),
+            Cell::Fiat(value) => write!(f, "{:.2}", value),
+        }
+    }
+}
+
+/// How a section counts towards the net result of the year.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Counts towards the income of the year.
+    Income,
+    /// Is deducted from the income of the year.
+    Expense,
+    /// Listed for reference only, like the staked principal.
+    Other,
+}
+
+/// A table of the report, written as its own file or sheet where the format allows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    /// Heading of the table.
+    pub title: String,
+    /// How the section counts towards the net result.
+    pub role: Role,
+    /// Column headings.
+    pub columns: Vec<String>,
+    /// Rows of cells, one per column.
+    pub rows: Vec<Vec<Cell>>,
+    /// Columns added up in a totals row. The last one is the value in the reporting
+    /// currency. If there are two, the first is the amount of the asset named by its column.
+    pub totals: Vec<usize>,
+}
+
+impl Section {
+    /// Sum of `column` over all rows.
+    pub fn total(&self, column: usize) -> f64 {
+        self.rows
+            .iter()
+            .filter_map(|row| row.get(column).and_then(Cell::value))
+            // Unlike `sum`, starts at 0.0 rather than -0.0
+            .fold(0.0, |total, value| total + value)
+    }
+
+    /// Column holding the asset amount in the totals, if the amounts are in one asset.
+    pub fn amount_total(&self) -> Option<usize> {
+        match self.totals[..] {
+            [amount, _fiat] => Some(amount),
+            _ => None,
+        }
+    }
+
+    /// Column holding the value in the reporting currency in the totals.
+    pub fn fiat_total(&self) -> Option<usize> {
+        self.totals.last().copied()
+    }
+}
+
+/// Everything reported for a year, independent of the output format. Formats meant to be
+/// read as a document also show the addresses, methodology and balance.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    /// Year the fiscal year of the report starts in.
+    pub year: i32,
+    /// Reporting currency, e.g. EUR.
+    pub currency: String,
+    /// Tax rules the report is laid out by.
+    pub profile: TaxProfile,
+    /// Tables of the report in the order they are written.
+    pub sections: Vec<Section>,
+    /// Income and fees of the year one by one, for formats importing individual events.
+    pub entries: Vec<Entry>,
+    /// Acquisitions and disposals of the reward assets of all years, for rules matching
+    /// disposals across years.
+    pub movements: Vec<Movement>,
+    /// Outgoing transfers of the year matched to the lots they dispose of.
+    pub disposals: Vec<Disposal>,
+    /// Reported addresses, by their role.
+    pub addresses: Vec<(String, String)>,
+    /// How events are classified and valued, a paragraph each.
+    pub methodology: Vec<String>,
+    /// Balance of the reward addresses at the start of the year, in `balance_symbol`.
+    pub opening_balance: f64,
+    /// Balance of the reward addresses after each event of the year.
+    pub balance: Vec<(NaiveDateTime, f64)>,
+    /// Symbol of the asset the balance is held in.
+    pub balance_symbol: String,
+}
+
+impl Report {
+    /// Lays out the reports for the fiscal year starting in `year` of the sorted `events`
+    /// of the addresses in `config`, priced by `pricer` and taxed by `profile`.
+    pub fn new(
+        events: &[RewardEvent],
+        year: i32,
+        config: &Config,
+        pricer: &Pricer,
+        profile: &TaxProfile,
+    ) -> Self {
+        let network = config.network;
+        let currency = &pricer.currency.to_string();
+        let name = profile.fiscal_year_name(year);
+        let entries = entries(events, year, profile, network);
+        let mut movements = movements(events, network);
+        // Rewards taxed when disposed of are acquired at no cost
+        let income = match profile.income_recognition {
+            IncomeRecognition::OnReceipt => Role::Income,
+            IncomeRecognition::OnDisposal => {
+                for movement in movements.iter_mut().filter(|m| m.income) {
+                    movement.price = 0.0;
+                }
+                Role::Other
+            }
+        };
+
+        let rewards = |category: Category, title: &str, role: Role, symbol: &str| {
+            let price = match category {
+                Category::Tokens => format!("{}_Price", currency),
+                _ => format!("{}_{}_Price", symbol, currency),
+            };
+            Section {
+                title: format!("{} {}", title, name),
+                role,
+                columns: ["Date", "Block", "Type", "ID", symbol, &price, currency]
+                    .map(String::from)
+                    .to_vec(),
+                rows: entries
+                    .iter()
+                    .filter(|entry| entry.category == category)
+                    .map(Entry::cells)
+                    .collect(),
+                // Token amounts are in different units and can't be added up
+                totals: match category {
+                    Category::Tokens => vec![6],
+                    _ => vec![4, 6],
+                },
+            }
+        };
+
+        // Received value that isn't income, listed for review
+        let unclassified = Section {
+            rows: events
+                .iter()
+                .filter(|event| profile.in_fiscal_year(&event.reward().date, year))
+                .filter_map(|event| match event {
+                    RewardEvent::Unclassified { reward } => Some(
+                        Entry::reward(network, Category::Execution, reward, "Unclassified").cells(),
+                    ),
+                    _ => None,
+                })
+                .collect(),
+            ..rewards(
+                Category::Execution,
+                "Unclassified Transfers",
+                Role::Other,
+                network.native_symbol(),
+            )
+        };
+
+        let staking_symbol = network.staking_symbol();
+        let principal = Section {
+            title: String::from("Principal"),
+            role: Role::Other,
+            columns: vec![
+                String::from("Validator"),
+                format!("Deposited_{}", staking_symbol),
+                format!("Deposited_{}", currency),
+                format!("Returned_{}", staking_symbol),
+                format!("Returned_{}", currency),
+            ],
+            rows: principal_by_validator(events)
+                .into_iter()
+                .map(|(validator, principal)| {
+                    vec![
+                        Cell::Text(validator),
+                        Cell::Amount(format_ether(principal.deposited).parse().unwrap()),
+                        Cell::Fiat(principal.deposited_fiat),
+                        Cell::Amount(format_ether(principal.returned).parse().unwrap()),
+                        Cell::Fiat(principal.returned_fiat),
+                    ]
+                })
+                .collect(),
+            totals: vec![],
+        };
+
+        let addresses = config.roles();
+
+        let history: Vec<(NaiveDateTime, f64)> = balance_history(events)
+            .into_iter()
+            .map(|(date, balance)| (date, format_ether(balance).parse().unwrap()))
+            .collect();
+
+        let (first, _) = profile.fiscal_year(year);
+        Report {
+            year,
+            currency: currency.to_string(),
+            profile: profile.clone(),
+            addresses,
+            methodology: methodology(year, pricer, profile),
+            opening_balance: history
+                .iter()
+                .take_while(|(date, _)| profile.local(date).date() < first)
+                .last()
+                .map(|(_, balance)| *balance)
+                .unwrap_or_default(),
+            balance: history
+                .into_iter()
+                .filter(|(date, _)| profile.in_fiscal_year(date, year))
+                .collect(),
+            balance_symbol: network.native_symbol().to_string(),
+            sections: vec![
+                rewards(
+                    Category::Execution,
+                    "Execution Rewards",
+                    income,
+                    network.native_symbol(),
+                ),
+                rewards(
+                    Category::Consensus,
+                    "Consensus Rewards",
+                    income,
+                    staking_symbol,
+                ),
+                rewards(
+                    Category::Fees,
+                    "Fees",
+                    Role::Expense,
+                    network.native_symbol(),
+                ),
+                rewards(Category::Tokens, "Token Rewards", income, "Amount"),
+                unclassified,
+                principal,
+                monthly(&entries, year, profile, currency),
+                price_sources(events, year, profile, pricer),
+            ],
+            entries,
+            disposals: disposals(&movements, profile.cost_basis)
+                .into_iter()
+                .filter(|disposal| profile.in_fiscal_year(&disposal.date, year))
+                .collect(),
+            movements,
+        }
+    }
+
+    /// Income less expenses of the year in the reporting currency.
+    pub fn net(&self) -> f64 {
+        self.summary()
+            .iter()
+            .map(|(section, total)| match section.role {
+                Role::Expense => -total,
+                _ => *total,
+            })
+            .sum()
+    }
+
+    /// Sections counting towards the net result with their total in the reporting currency.
+    pub fn summary(&self) -> Vec<(&Section, f64)> {
+        self.sections
+            .iter()
+            .filter(|section| section.role != Role::Other)
+            .filter_map(|section| Some((section, section.total(section.fiat_total()?))))
+            .collect()
+    }
+
+    /// The `index`th month of the fiscal year, counting from 0.
+    pub fn month(&self, index: u32) -> chrono::Month {
+        let (first, _) = self.profile.fiscal_year_start;
+        chrono::Month::try_from(((first - 1 + index) % 12 + 1) as u8).unwrap()
+    }
+
+    /// The balance as a step line of `(fraction of the year, balance)` points, running
+    /// from the opening balance to the end of the fiscal year.
+    pub fn balance_steps(&self) -> Vec<(f64, f64)> {
+        let (first, last) = self.profile.fiscal_year(self.year);
+        let start = first.and_hms_opt(0, 0, 0).unwrap();
+        let end = last.succ_opt().unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let length = (end - start).num_seconds() as f64;
+
+        let mut steps = vec![];
+        let mut balance = self.opening_balance;
+        for (date, next) in &self.balance {
+            let x = (self.profile.local(date) - start).num_seconds() as f64 / length;
+            steps.push((x, balance));
+            steps.push((x, *next));
+            balance = *next;
+        }
+        steps.insert(0, (0.0, self.opening_balance));
+        steps.push((1.0, balance));
+        steps
+    }
+}
+
+/// Income and expenses per month of the fiscal year in the reporting currency.
+fn monthly(entries: &[Entry], year: i32, profile: &TaxProfile, currency: &str) -> Section {
+    let categories = [
+        Category::Execution,
+        Category::Consensus,
+        Category::Tokens,
+        Category::Fees,
+    ];
+    let mut columns = vec![String::from("Month")];
+    for name in ["Execution", "Consensus", "Tokens", "Fees", "Net"] {
+        columns.push(format!("{}_{}", name, currency));
+    }
+
+    // A fiscal year starting mid-month touches 13 months
+    let (first, last) = profile.fiscal_year(year);
+    let mut months = vec![];
+    let mut month = first.with_day(1).unwrap();
+    while month <= last {
+        months.push((month.year(), month.month()));
+        month = month.checked_add_months(chrono::Months::new(1)).unwrap();
+    }
+
+    let rows = months
+        .into_iter()
+        .map(|(year, month)| {
+            let in_month = |category: Category| -> f64 {
+                entries
+                    .iter()
+                    .filter(|entry| {
+                        let local = profile.local(&entry.date);
+                        entry.category == category && (local.year(), local.month()) == (year, month)
+                    })
+                    .map(|entry| entry.fiat)
+                    .fold(0.0, |total, fiat| total + fiat)
+            };
+            let values = categories.map(in_month);
+            let net = values[0] + values[1] + values[2] - values[3];
+
+            let mut row = vec![Cell::Text(format!("{}-{:02}", year, month))];
+            row.extend(values.into_iter().chain([net]).map(Cell::Fiat));
+            row
+        })
+        .collect();
+
+    Section {
+        title: format!("Monthly {}", profile.fiscal_year_name(year)),
+        role: Role::Other,
+        columns,
+        rows,
+        totals: vec![1, 2, 3, 4, 5],
+    }
+}
+
+/// Where the price of each asset valued in the fiscal year `year` comes from.
+fn price_sources(
+    events: &[RewardEvent],
+    year: i32,
+    profile: &TaxProfile,
+    pricer: &Pricer,
+) -> Section {
+    let mut days: BTreeMap<&str, BTreeSet<NaiveDate>> = BTreeMap::new();
+    for reward in events.iter().flat_map(RewardEvent::rewards) {
+        if profile.in_fiscal_year(&reward.date, year) {
+            days.entry(&reward.asset.price_id)
+                .or_default()
+                .insert(pricer.policy.price_date(&reward.date));
+        }
+    }
+
+    Section {
+        title: String::from("Price Sources"),
+        role: Role::Other,
+        columns: ["Asset", "Source", "Currency", "Days"]
+            .map(String::from)
+            .to_vec(),
+        rows: days
+            .into_iter()
+            .map(|(price_id, days)| {
+                vec![
+                    Cell::Text(price_id.to_string()),
+                    Cell::Text(format!("CoinGecko /coins/{}/history", price_id)),
+                    Cell::Text(pricer.currency.to_string()),
+                    Cell::Integer(days.len() as u64),
+                ]
+            })
+            .collect(),
+        totals: vec![],
+    }
+}
+
+fn methodology(year: i32, pricer: &Pricer, profile: &TaxProfile) -> Vec<String> {
+    let (first, last) = profile.fiscal_year(year);
+    let price = match pricer.policy {
+        PricePolicy::StartOfDay => "at 00:00 UTC of the day it was received",
+        PricePolicy::EndOfDay => {
+            "at 00:00 UTC of the day after it was received, i.e. the closing price"
+        }
+    };
+    vec![
+        String::from(
+            "Rewards are listed when they are received. Execution rewards are the priority \
+             fees of proposed blocks, MEV payments, splitter distributions and smoothing pool \
+             claims. Consensus rewards are the withdrawals of validator balances above the \
+             principal, minipool distributions and the yield of liquid staking tokens, by rebase \
+             or exchange rate gain.",
+        ),
+        format!(
+            "The report covers the fiscal year from {} to {} of the {} tax profile, with days \
+             counted at UTC{}.",
+            first, last, profile.name, profile.utc_offset
+        ),
+        format!(
+            "Each reward is valued at the daily {} price of its asset on CoinGecko {}.",
+            pricer.currency, price
+        ),
+        String::from(
+            "Fees are the gas of outgoing transactions and the fees of consolidation and \
+             withdrawal requests, valued like rewards.",
+        ),
+        String::from(
+            "Deposits and returned principal are not income. They are listed per validator \
+             for reference.",
+        ),
+    ]
+}
+
+/// Output format of a [`Report`].
+pub trait ReportWriter {
+    /// Writes `report` to one or more files in `directory`.
+    fn write(&self, report: &Report, directory: &Path) -> Result<(), Box<dyn Error>>;
+}
+
+/// Formats selectable with `--format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// Comma-separated values, one file per section.
+    Csv,
+    /// The whole report as JSON.
+    Json,
+    /// Excel workbook, a sheet per section.
+    Xlsx,
+    /// Markdown document.
+    Markdown,
+    /// HTML document.
+    Html,
+    /// PDF document.
+    Pdf,
+    /// Import files of commercial crypto tax tools.
+    Koinly,
+    /// CoinTracking import.
+    CoinTracking,
+    /// Blockpit import.
+    Blockpit,
+    /// Plain-text accounting ledgers.
+    Beancount,
+    /// hledger journal.
+    Hledger,
+    /// Booking batch for German tax advisors.
+    Datev,
+    /// Figures for the German tax return.
+    AnlageSo,
+    /// Figures for the US tax return.
+    Us,
+    /// Capital gains under the UK matching rules, per tax year with `TAX_PROFILE=uk`.
+    Uk,
+}
+
+impl ReportFormat {
+    /// Writer rendering the report in this format.
+    pub fn writer(&self) -> Box<dyn ReportWriter> {
+        match self {
+            ReportFormat::Csv => Box::new(CsvReport),
+            ReportFormat::Json => Box::new(JsonReport),
+            ReportFormat::Xlsx => Box::new(XlsxReport),
+            ReportFormat::Markdown => Box::new(MarkdownReport),
+            ReportFormat::Html => Box::new(HtmlReport),
+            ReportFormat::Pdf => Box::new(PdfReport),
+            ReportFormat::Koinly => Box::new(KoinlyReport),
+            ReportFormat::CoinTracking => Box::new(CoinTrackingReport),
+            ReportFormat::Blockpit => Box::new(BlockpitReport),
+            ReportFormat::Beancount => Box::new(BeancountReport),
+            ReportFormat::Hledger => Box::new(HledgerReport),
+            ReportFormat::Datev => Box::new(DatevReport),
+            ReportFormat::AnlageSo => Box::new(AnlageSoReport),
+            ReportFormat::Us => Box::new(UsReport),
+            ReportFormat::Uk => Box::new(UkReport),
+        }
+    }
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "csv" => Ok(ReportFormat::Csv),
+            "json" => Ok(ReportFormat::Json),
+            "xlsx" => Ok(ReportFormat::Xlsx),
+            "md" | "markdown" => Ok(ReportFormat::Markdown),
+            "html" => Ok(ReportFormat::Html),
+            "pdf" => Ok(ReportFormat::Pdf),
+            "koinly" => Ok(ReportFormat::Koinly),
+            "cointracking" => Ok(ReportFormat::CoinTracking),
+            "blockpit" => Ok(ReportFormat::Blockpit),
+            "beancount" => Ok(ReportFormat::Beancount),
+            "hledger" | "journal" => Ok(ReportFormat::Hledger),
+            "datev" => Ok(ReportFormat::Datev),
+            "anlage_so" | "anlage-so" => Ok(ReportFormat::AnlageSo),
+            "us" => Ok(ReportFormat::Us),
+            "uk" => Ok(ReportFormat::Uk),
+            other => Err(format!("Unknown report format {}", other)),
+        }
+    }
+}
+
+/// Writes the reports for the fiscal year starting in `year` of the sorted `events` of the
+/// addresses in `config` to `directory` in each of `formats`.
+pub fn write_reports(
+    events: &[RewardEvent],
+    year: i32,
+    config: &Config,
+    pricer: &Pricer,
+    profile: &TaxProfile,
+    directory: &Path,
+    formats: &[ReportFormat],
+) -> Result<(), Box<dyn Error>> {
+    let report = Report::new(events, year, config, pricer, profile);
+    for format in formats {
+        format.writer().write(&report, directory)?;
+    }
+    Ok(())
+}
+
+/// A consensus withdrawal of 0.05 ETH on 2023-09-01, for tests of the entry exports.
+#[cfg(test)]
+pub(crate) fn sample_entry() -> Entry {
+    Entry {
+        category: Category::Consensus,
+        date: NaiveDate::from_ymd_opt(2023, 9, 1)
+            .unwrap()
+            .and_hms_opt(12, 30, 0)
+            .unwrap(),
+        block: 18_000_000.into(),
+        kind: String::from("Withdrawal"),
+        id: String::from("42"),
+        amount: String::from("0.050000000000000000"),
+        symbol: String::from("ETH"),
+        address: String::from("0x11"),
+        price: 1520.12,
+        fiat: 76.006,
+    }
+}
+
+/// Runs the whole pipeline against `fixtures/`. Set `UPDATE_GOLDEN=true` to regenerate
+/// the golden files after an intended change of the output.
+#[tokio::test]
+async fn test_reports_match_golden_files() {
+    crate::fixtures::setup();
+
+    let config = crate::addresses::Config::from_env().unwrap();
+    let mut events = crate::processing::process_transactions(&config)
+        .await
+        .unwrap();
+    let pricer = Pricer::default();
+    pricer.price_events(&mut events).await.unwrap();
+    events.sort();
+
+    let directory =
+        std::env::temp_dir().join(format!("staking-tax-test-reports-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    write_reports(
+        &events,
+        2023,
+        &config,
+        &pricer,
+        &TaxProfile::default(),
+        &directory,
+        &[ReportFormat::Csv],
+    )
+    .unwrap();
+
+    let golden = crate::fixtures::fixtures_dir().join("golden");
+    for name in [
+        "Execution Rewards 2023.csv",
+        "Consensus Rewards 2023.csv",
+        "Fees 2023.csv",
+        "Token Rewards 2023.csv",
+        "Principal.csv",
+    ] {
+        let output = std::fs::read_to_string(directory.join(name)).unwrap();
+        if std::env::var("UPDATE_GOLDEN").is_ok_and(|update| update == "true") {
+            std::fs::write(golden.join(name), &output).unwrap();
+            continue;
+        }
+        assert_eq!(
+            output,
+            std::fs::read_to_string(golden.join(name)).unwrap(),
+            "{} differs from its golden file",
+            name
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! Rocket Pool minipool distributions and smoothing pool claims.
+
+use crate::addresses::Config;
+use crate::etherscan::internal_transactions;
+use crate::etherscan_v2::{Client, Error};
+use crate::network::Network;
+use crate::types::{Reward, RewardEvent};
+use ethers::types::{Address, H256, U256};
+use ethers::utils::parse_ether;
+use std::collections::HashMap;
+
+/// Minipool balances of at least this amount (in ETH) are distributed as an exit by the
+/// minipool contract, smaller ones as skimmed rewards. The balance is split between the node
+/// and the rETH holders, so it's the sum of both shares.
+pub const MINIPOOL_EXIT_THRESHOLD: u64 = 8;
+
+/// Rocket Pool node configuration.
+///
+/// Consensus rewards of a minipool are paid to the minipool contract and split between the node
+/// operator and the rETH holders on `distributeBalance()`, so only the distributions reaching the
+/// node's withdrawal address are income. Smoothing pool rewards arrive as periodic claims from the
+/// merkle distributor, RPL rewards from the same claims are picked up as token rewards.
+#[derive(Debug, Clone)]
+pub struct RocketPool {
+    pub withdrawal_address: Address,
+    /// Minipool contracts and the node operator's bond in each of them.
+    pub minipools: HashMap<Address, U256>,
+    /// Merkle distributor contracts paying out smoothing pool and RPL rewards.
+    pub distributors: Vec<Address>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RocketPoolTransfer {
+    /// Operator share of a minipool balance. `bond` is non-zero if the distribution
+    /// followed an exit and returned the operator's bond.
+    MinipoolDistribution {
+        income: U256,
+        bond: U256,
+    },
+    SmoothingPoolClaim,
+}
+
+impl RocketPool {
+    /// Rocket Pool mode for the minipools in `config`, disabled if there are none.
+    pub fn new(config: &Config) -> Option<Self> {
+        if config.rocket_pool_minipools.is_empty() {
+            return None;
+        }
+
+        Some(RocketPool {
+            withdrawal_address: config.consensus_address,
+            minipools: config.rocket_pool_minipools.clone(),
+            distributors: config.rocket_pool_distributors.clone(),
+        })
+    }
+
+    /// Whether transfers sent by `from` are accounted for by Rocket Pool mode.
+    pub fn handles(&self, from: &Address) -> bool {
+        self.minipools.contains_key(from) || self.distributors.contains(from)
+    }
+
+    /// Classifies an ETH transfer of `value` from `from` to the node's withdrawal address,
+    /// out of a `distributed` minipool balance.
+    ///
+    /// A minipool distributing at least `MINIPOOL_EXIT_THRESHOLD` is treated as exited and
+    /// returns up to the operator's bond, so a lossy exit returns less than the bond.
+    pub fn classify(
+        &self,
+        from: &Address,
+        value: U256,
+        distributed: U256,
+    ) -> Option<RocketPoolTransfer> {
+        if let Some(bond) = self.minipools.get(from) {
+            let bond = if distributed >= parse_ether(MINIPOOL_EXIT_THRESHOLD).unwrap() {
+                value.min(*bond)
+            } else {
+                U256::zero()
+            };
+            Some(RocketPoolTransfer::MinipoolDistribution {
+                income: value - bond,
+                bond,
+            })
+        } else if self.distributors.contains(from) {
+            Some(RocketPoolTransfer::SmoothingPoolClaim)
+        } else {
+            None
+        }
+    }
+}
+
+pub async fn rocket_pool_events(
+    client: &Client,
+    rocket_pool: &RocketPool,
+    network: Network,
+) -> Result<Vec<RewardEvent>, Error> {
+    let mut rewards: Vec<RewardEvent> = vec![];
+
+    // Balances distributed by each minipool, the node's and the rETH holders' shares
+    let mut distributed: HashMap<H256, U256> = HashMap::new();
+    for minipool in rocket_pool.minipools.keys() {
+        for tx in internal_transactions(client, *minipool).await? {
+            if tx.is_error == "0" && tx.from == *minipool {
+                *distributed.entry(tx.hash).or_default() += tx.value;
+            }
+        }
+    }
+
+    for tx in internal_transactions(client, rocket_pool.withdrawal_address).await? {
+        if tx.is_error != "0" || tx.to.value() != Some(&rocket_pool.withdrawal_address) {
+            continue;
+        }
+
+        let balance = distributed.get(&tx.hash).copied().unwrap_or(tx.value);
+        match rocket_pool.classify(&tx.from, tx.value, balance) {
+            Some(RocketPoolTransfer::MinipoolDistribution { income, bond }) => {
+                if !income.is_zero() {
+                    rewards.push(RewardEvent::MinipoolDistribution {
+                        reward: Reward::new(
+                            tx.block_number.as_number().unwrap(),
+                            format!("{:?}", tx.hash),
+                            tx.time_stamp.clone(),
+                            income,
+                            network.native_asset(),
+                            rocket_pool.withdrawal_address,
+                        ),
+                    });
+                }
+                if !bond.is_zero() {
+                    rewards.push(RewardEvent::BondReturn {
+                        reward: Reward::new(
+                            tx.block_number.as_number().unwrap(),
+                            format!("{:?}", tx.hash),
+                            tx.time_stamp,
+                            bond,
+                            network.native_asset(),
+                            rocket_pool.withdrawal_address,
+                        ),
+                    });
+                }
+            }
+            Some(RocketPoolTransfer::SmoothingPoolClaim) => {
+                rewards.push(RewardEvent::SmoothingPoolClaim {
+                    reward: Reward::new(
+                        tx.block_number.as_number().unwrap(),
+                        format!("{:?}", tx.hash),
+                        tx.time_stamp,
+                        tx.value,
+                        network.native_asset(),
+                        rocket_pool.withdrawal_address,
+                    ),
+                });
+            }
+            None => {}
+        }
+    }
+
+    Ok(rewards)
+}
+
+#[test]
+fn test_classify_rocket_pool_transfer() {
+    let minipool = Address::from_low_u64_be(1);
+    let distributor = Address::from_low_u64_be(2);
+    let rocket_pool = RocketPool {
+        withdrawal_address: Address::from_low_u64_be(3),
+        minipools: HashMap::from([(minipool, parse_ether(8).unwrap())]),
+        distributors: vec![distributor],
+    };
+
+    assert_eq!(
+        rocket_pool.classify(
+            &minipool,
+            parse_ether("0.5").unwrap(),
+            parse_ether("2").unwrap()
+        ),
+        Some(RocketPoolTransfer::MinipoolDistribution {
+            income: parse_ether("0.5").unwrap(),
+            bond: U256::zero(),
+        })
+    );
+    assert_eq!(
+        rocket_pool.classify(
+            &minipool,
+            parse_ether("8.25").unwrap(),
+            parse_ether("32.25").unwrap()
+        ),
+        Some(RocketPoolTransfer::MinipoolDistribution {
+            income: parse_ether("0.25").unwrap(),
+            bond: parse_ether(8).unwrap(),
+        })
+    );
+
+    // Exit of a 16 ETH minipool with a penalty, returning less than the bond
+    let lossy = Address::from_low_u64_be(4);
+    let rocket_pool = RocketPool {
+        minipools: HashMap::from([(lossy, parse_ether(16).unwrap())]),
+        ..rocket_pool
+    };
+    assert_eq!(
+        rocket_pool.classify(&lossy, parse_ether(14).unwrap(), parse_ether(30).unwrap()),
+        Some(RocketPoolTransfer::MinipoolDistribution {
+            income: U256::zero(),
+            bond: parse_ether(14).unwrap(),
+        })
+    );
+
+    // Exit of an 8 ETH minipool with a penalty, paying the node less than the threshold
+    let leb8 = Address::from_low_u64_be(5);
+    let rocket_pool = RocketPool {
+        minipools: HashMap::from([(leb8, parse_ether(8).unwrap())]),
+        ..rocket_pool
+    };
+    assert_eq!(
+        rocket_pool.classify(
+            &leb8,
+            parse_ether("7.5").unwrap(),
+            parse_ether("31.5").unwrap()
+        ),
+        Some(RocketPoolTransfer::MinipoolDistribution {
+            income: U256::zero(),
+            bond: parse_ether("7.5").unwrap(),
+        })
+    );
+    assert_eq!(
+        rocket_pool.classify(
+            &distributor,
+            parse_ether(1).unwrap(),
+            parse_ether(1).unwrap()
+        ),
+        Some(RocketPoolTransfer::SmoothingPoolClaim)
+    );
+    assert_eq!(
+        rocket_pool.classify(&Address::zero(), U256::one(), U256::one()),
+        None
+    );
+}
@@ -0,0 +1,177 @@
+//! JSON-RPC transport for ethers providers over the shared HTTP client.
+
+use crate::http::{HttpClient, HttpError};
+use crate::network::Network;
+use crate::rate_limit::RateLimiter;
+use async_trait::async_trait;
+use dotenv::dotenv;
+use ethers::providers::{JsonRpcClient, JsonRpcError, Provider, ProviderError, RpcError};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+
+#[derive(Debug)]
+pub enum RpcClientError {
+    Http(HttpError),
+    Json(serde_json::Error),
+    /// The node answered with an error.
+    Rpc(JsonRpcError),
+}
+
+impl fmt::Display for RpcClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpcClientError::Http(error) => write!(f, "RPC request failed: {}", error),
+            RpcClientError::Json(error) => write!(f, "Invalid RPC response: {}", error),
+            RpcClientError::Rpc(error) => write!(f, "RPC error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for RpcClientError {}
+
+impl RpcError for RpcClientError {
+    fn as_error_response(&self) -> Option<&JsonRpcError> {
+        match self {
+            RpcClientError::Rpc(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    fn as_serde_error(&self) -> Option<&serde_json::Error> {
+        match self {
+            RpcClientError::Json(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<RpcClientError> for ProviderError {
+    fn from(error: RpcClientError) -> Self {
+        ProviderError::JsonRpcClientError(Box::new(error))
+    }
+}
+
+impl From<HttpError> for RpcClientError {
+    fn from(error: HttpError) -> Self {
+        RpcClientError::Http(error)
+    }
+}
+
+impl From<serde_json::Error> for RpcClientError {
+    fn from(error: serde_json::Error) -> Self {
+        RpcClientError::Json(error)
+    }
+}
+
+/// Response to a single request, holding either a `result` or an `error`.
+#[derive(Debug, Deserialize)]
+struct Response {
+    #[serde(default)]
+    result: serde_json::Value,
+    error: Option<JsonRpcError>,
+}
+
+/// Sends JSON-RPC requests with the shared `HttpClient`, so calls to the node are rate
+/// limited, retried and suspended by the circuit breaker like all other data sources.
+#[derive(Debug)]
+pub struct RpcClient {
+    http: HttpClient,
+    url: String,
+    id: AtomicU64,
+}
+
+impl RpcClient {
+    pub fn new(url: impl Into<String>) -> Self {
+        RpcClient {
+            http: rpc_http().clone(),
+            url: url.into(),
+            id: AtomicU64::new(1),
+        }
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for RpcClient {
+    type Error = RpcClientError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, RpcClientError>
+    where
+        T: fmt::Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": self.id.fetch_add(1, Ordering::Relaxed),
+            "method": method,
+            "params": serde_json::to_value(params)?,
+        });
+        let body = self.http.post_json(&self.url, &request).await?;
+
+        let response: Response = serde_json::from_str(&body)?;
+        match response.error {
+            Some(error) => Err(RpcClientError::Rpc(error)),
+            None => Ok(serde_json::from_value(response.result)?),
+        }
+    }
+}
+
+fn rpc_http() -> &'static HttpClient {
+    static HTTP: OnceLock<HttpClient> = OnceLock::new();
+    HTTP.get_or_init(|| HttpClient::new("RPC", RateLimiter::from_env("RPC", "RPC", 10.0)))
+}
+
+/// Provider for `RPC_URL`, Infura on mainnet if `INFURA_API_KEY` is set, or the public
+/// endpoint of the network.
+pub fn rpc_provider(network: Network) -> Provider<RpcClient> {
+    dotenv().ok();
+
+    let var = |name: &str| dotenv::var(name).ok().filter(|value| !value.is_empty());
+    let url = match (var("RPC_URL"), var("INFURA_API_KEY")) {
+        (Some(url), _) => url,
+        (None, Some(infura_api_key)) if network == Network::Mainnet => {
+            format!("https://mainnet.infura.io/v3/{}", infura_api_key)
+        }
+        _ => network.public_rpc_url().to_string(),
+    };
+
+    Provider::new(RpcClient::new(url))
+}
+
+#[tokio::test]
+async fn test_rpc_client() {
+    use ethers::providers::Middleware;
+    use ethers::types::U64;
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for body in [
+            r#"{"jsonrpc":"2.0","id":1,"result":"0x10"}"#,
+            r#"{"jsonrpc":"2.0","id":2,"error":{"code":-32000,"message":"header not found"}}"#,
+        ] {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            assert!(stream.read(&mut request).unwrap() > 0);
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                 Connection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        }
+    });
+
+    let provider = Provider::new(RpcClient::new(url));
+    assert_eq!(provider.get_block_number().await.unwrap(), U64::from(16));
+    let error = provider.get_block_number().await.unwrap_err();
+    assert_eq!(
+        error.as_error_response().map(|error| error.code),
+        Some(-32000)
+    );
+}
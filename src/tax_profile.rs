@@ -0,0 +1,497 @@
+//! Tax rules of a jurisdiction the reports are laid out by, selected with `TAX_PROFILE`.
+//!
+//! The built-in profiles can be adjusted field by field from the environment, which also
+//! makes up custom profiles, e.g. another country starting from the closest built-in one.
+
+use crate::lots::Disposal;
+use crate::pricing::Currency;
+use crate::types::RewardEvent;
+use chrono::{Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime};
+use dotenv::dotenv;
+use std::str::FromStr;
+
+/// Jurisdictions with a built-in profile.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Jurisdiction {
+    #[default]
+    /// Germany.
+    De,
+    /// Austria.
+    At,
+    /// Switzerland.
+    Ch,
+    /// United Kingdom.
+    Uk,
+    /// United States.
+    Us,
+}
+
+impl FromStr for Jurisdiction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "de" => Ok(Jurisdiction::De),
+            "at" => Ok(Jurisdiction::At),
+            "ch" => Ok(Jurisdiction::Ch),
+            "uk" | "gb" => Ok(Jurisdiction::Uk),
+            "us" => Ok(Jurisdiction::Us),
+            other => Err(format!("Unknown tax profile {}", other)),
+        }
+    }
+}
+
+/// When rewards are taxed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncomeRecognition {
+    /// As income at their value when received, which is also their cost.
+    OnReceipt,
+    /// Not when received, but acquired at no cost and taxed as gains when disposed of.
+    OnDisposal,
+}
+
+impl FromStr for IncomeRecognition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "on_receipt" => Ok(IncomeRecognition::OnReceipt),
+            "on_disposal" => Ok(IncomeRecognition::OnDisposal),
+            other => Err(format!("Unknown income recognition {}", other)),
+        }
+    }
+}
+
+/// How disposals are matched to acquisitions, see `lots::disposals`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CostBasis {
+    /// First in, first out.
+    Fifo,
+    /// Moving average cost of all units held.
+    Average,
+    /// Same-day and 30-day matching before a pool at average cost, as by HMRC.
+    Section104,
+}
+
+impl FromStr for CostBasis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "fifo" => Ok(CostBasis::Fifo),
+            "average" => Ok(CostBasis::Average),
+            "section104" => Ok(CostBasis::Section104),
+            other => Err(format!("Unknown cost basis {}", other)),
+        }
+    }
+}
+
+/// Tax free part of income or gains.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Exemption {
+    /// Fully taxable.
+    None,
+    /// Tax free below the amount, fully taxable from it on, like the German Freigrenze.
+    Threshold(f64),
+    /// The amount is deducted, like the UK annual exempt amount.
+    Allowance(f64),
+    /// Always tax free, like private capital gains in Switzerland.
+    Exempt,
+}
+
+impl Exemption {
+    /// The threshold or allowance, zero if there's none.
+    pub fn amount(&self) -> f64 {
+        match self {
+            Exemption::Threshold(amount) | Exemption::Allowance(amount) => *amount,
+            Exemption::None | Exemption::Exempt => 0.0,
+        }
+    }
+
+    /// Taxable part of `amount`.
+    pub fn taxable(&self, amount: f64) -> f64 {
+        match self {
+            Exemption::None => amount,
+            Exemption::Threshold(threshold) if amount < *threshold => 0.0,
+            Exemption::Threshold(_) => amount,
+            Exemption::Allowance(allowance) => (amount - allowance).max(0.0),
+            Exemption::Exempt => 0.0,
+        }
+    }
+}
+
+/// Given as `none`, `exempt`, `threshold:<amount>` or `allowance:<amount>`.
+impl FromStr for Exemption {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let amount = |value: &str| {
+            value
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid exemption amount {}", value))
+        };
+        match s.split_once(':') {
+            Some(("threshold", value)) => Ok(Exemption::Threshold(amount(value)?)),
+            Some(("allowance", value)) => Ok(Exemption::Allowance(amount(value)?)),
+            None if s == "none" => Ok(Exemption::None),
+            None if s == "exempt" => Ok(Exemption::Exempt),
+            _ => Err(format!("Unknown exemption {}", s)),
+        }
+    }
+}
+
+/// Tax rules of a jurisdiction: fiscal year, currency, income recognition and cost basis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaxProfile {
+    /// Name of the jurisdiction as shown on the reports.
+    pub name: String,
+    /// Month and day the fiscal year starts on.
+    pub fiscal_year_start: (u32, u32),
+    /// Currency the tax return is filed in.
+    pub currency: Currency,
+    /// Offset from UTC days and the fiscal year are counted in. Daylight saving time
+    /// isn't applied.
+    pub utc_offset: FixedOffset,
+    /// When rewards count as income.
+    pub income_recognition: IncomeRecognition,
+    /// How disposed units are matched to acquisitions.
+    pub cost_basis: CostBasis,
+    /// Units held longer than this many months are disposed of long-term, if the
+    /// jurisdiction tells them apart.
+    pub holding_period: Option<u32>,
+    /// Whether gains on long-term disposals are tax free.
+    pub long_term_exempt: bool,
+    /// Exemption of the income of a fiscal year.
+    pub income_exemption: Exemption,
+    /// Exemption of the gains of a fiscal year, by the first year it applies to.
+    pub gains_exemptions: Vec<(i32, Exemption)>,
+}
+
+impl TaxProfile {
+    /// Profile of `jurisdiction` with the rules as built in.
+    pub fn builtin(jurisdiction: Jurisdiction) -> Self {
+        let hours = |hours: i32| FixedOffset::east_opt(hours * 3600).unwrap();
+        match jurisdiction {
+            Jurisdiction::De => TaxProfile {
+                name: String::from("DE"),
+                fiscal_year_start: (1, 1),
+                currency: Currency::Eur,
+                utc_offset: hours(1),
+                income_recognition: IncomeRecognition::OnReceipt,
+                cost_basis: CostBasis::Fifo,
+                holding_period: Some(12),
+                long_term_exempt: true,
+                income_exemption: Exemption::Threshold(256.0),
+                gains_exemptions: vec![
+                    (i32::MIN, Exemption::Threshold(600.0)),
+                    (2024, Exemption::Threshold(1000.0)),
+                ],
+            },
+            Jurisdiction::At => TaxProfile {
+                name: String::from("AT"),
+                fiscal_year_start: (1, 1),
+                currency: Currency::Eur,
+                utc_offset: hours(1),
+                income_recognition: IncomeRecognition::OnDisposal,
+                cost_basis: CostBasis::Average,
+                holding_period: None,
+                long_term_exempt: false,
+                income_exemption: Exemption::None,
+                gains_exemptions: vec![(i32::MIN, Exemption::None)],
+            },
+            Jurisdiction::Ch => TaxProfile {
+                name: String::from("CH"),
+                fiscal_year_start: (1, 1),
+                currency: Currency::Chf,
+                utc_offset: hours(1),
+                income_recognition: IncomeRecognition::OnReceipt,
+                cost_basis: CostBasis::Average,
+                holding_period: None,
+                long_term_exempt: false,
+                income_exemption: Exemption::None,
+                gains_exemptions: vec![(i32::MIN, Exemption::Exempt)],
+            },
+            Jurisdiction::Uk => TaxProfile {
+                name: String::from("UK"),
+                fiscal_year_start: (4, 6),
+                currency: Currency::Gbp,
+                utc_offset: hours(0),
+                income_recognition: IncomeRecognition::OnReceipt,
+                cost_basis: CostBasis::Section104,
+                holding_period: None,
+                long_term_exempt: false,
+                income_exemption: Exemption::None,
+                gains_exemptions: vec![
+                    (i32::MIN, Exemption::Allowance(12300.0)),
+                    (2023, Exemption::Allowance(6000.0)),
+                    (2024, Exemption::Allowance(3000.0)),
+                ],
+            },
+            Jurisdiction::Us => TaxProfile {
+                name: String::from("US"),
+                fiscal_year_start: (1, 1),
+                currency: Currency::Usd,
+                utc_offset: hours(-5),
+                income_recognition: IncomeRecognition::OnReceipt,
+                cost_basis: CostBasis::Fifo,
+                holding_period: Some(12),
+                long_term_exempt: false,
+                income_exemption: Exemption::None,
+                gains_exemptions: vec![(i32::MIN, Exemption::None)],
+            },
+        }
+    }
+
+    /// The profile named by `TAX_PROFILE` (defaults to DE), with any field overridden by
+    /// `CURRENCY` and the other `TAX_*` variables.
+    pub fn from_env() -> Result<Self, String> {
+        dotenv().ok();
+
+        let var = |name: &str| dotenv::var(name).ok().filter(|value| !value.is_empty());
+        let mut profile = Self::builtin(match var("TAX_PROFILE") {
+            Some(profile) => profile.parse()?,
+            None => Jurisdiction::default(),
+        });
+        if let Some(start) = var("TAX_FISCAL_YEAR_START") {
+            profile.fiscal_year_start = parse_month_day(&start)?;
+        }
+        if let Some(currency) = var("CURRENCY") {
+            profile.currency = currency.parse()?;
+        }
+        if let Some(offset) = var("TAX_UTC_OFFSET") {
+            profile.utc_offset = parse_offset(&offset)?;
+        }
+        if let Some(recognition) = var("TAX_INCOME_RECOGNITION") {
+            profile.income_recognition = recognition.parse()?;
+        }
+        if let Some(cost_basis) = var("TAX_COST_BASIS") {
+            profile.cost_basis = cost_basis.parse()?;
+        }
+        if let Some(months) = var("TAX_HOLDING_PERIOD_MONTHS") {
+            profile.holding_period = match months.trim() {
+                "none" => None,
+                months => Some(months.parse().map_err(|_| {
+                    format!(
+                        "Invalid TAX_HOLDING_PERIOD_MONTHS {}, expected months or none",
+                        months
+                    )
+                })?),
+            };
+        }
+        if let Some(exempt) = var("TAX_LONG_TERM_EXEMPT") {
+            profile.long_term_exempt = exempt.trim() == "true";
+        }
+        if let Some(exemption) = var("TAX_INCOME_EXEMPTION") {
+            profile.income_exemption = exemption.parse()?;
+        }
+        if let Some(exemption) = var("TAX_GAINS_EXEMPTION") {
+            profile.gains_exemptions = vec![(i32::MIN, exemption.parse()?)];
+        }
+        Ok(profile)
+    }
+
+    /// `date` in UTC as local time of the jurisdiction.
+    pub fn local(&self, date: &NaiveDateTime) -> NaiveDateTime {
+        *date + Duration::seconds(self.utc_offset.local_minus_utc() as i64)
+    }
+
+    /// First and last day of the fiscal year starting in `year`. A fiscal year starting on
+    /// 02-29 starts on the last day of February in common years.
+    pub fn fiscal_year(&self, year: i32) -> (NaiveDate, NaiveDate) {
+        let (month, day) = self.fiscal_year_start;
+        let start = |year: i32| {
+            (1..=day)
+                .rev()
+                .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+                .unwrap()
+        };
+        (start(year), start(year + 1).pred_opt().unwrap())
+    }
+
+    /// Year the last fiscal year completed before the local day `today` starts in.
+    pub fn last_completed_year(&self, today: NaiveDate) -> i32 {
+        let (first, _) = self.fiscal_year(today.year());
+        match today >= first {
+            true => today.year() - 1,
+            false => today.year() - 2,
+        }
+    }
+
+    /// Whether `date` in UTC is in the fiscal year starting in `year`.
+    pub fn in_fiscal_year(&self, date: &NaiveDateTime, year: i32) -> bool {
+        let (first, last) = self.fiscal_year(year);
+        let local = self.local(date).date();
+        first <= local && local <= last
+    }
+
+    /// Name of the fiscal year starting in `year`, e.g. 2023 or 2023-24.
+    pub fn fiscal_year_name(&self, year: i32) -> String {
+        match self.fiscal_year_start {
+            (1, 1) => year.to_string(),
+            _ => format!("{}-{:02}", year, (year + 1) % 100),
+        }
+    }
+
+    /// Exemption of the gains of the fiscal year starting in `year`.
+    pub fn gains_exemption(&self, year: i32) -> Exemption {
+        self.gains_exemptions
+            .iter()
+            .rfind(|(from, _)| *from <= year)
+            .map(|(_, exemption)| *exemption)
+            .unwrap_or(Exemption::None)
+    }
+
+    /// Whether the units of `disposal` were held longer than the holding period, if the
+    /// jurisdiction has one and their acquisition is known.
+    pub fn long_term(&self, disposal: &Disposal) -> Option<bool> {
+        let months = self.holding_period?;
+        let anniversary = disposal
+            .acquired?
+            .checked_add_months(chrono::Months::new(months))
+            .unwrap();
+        Some(disposal.date > anniversary)
+    }
+
+    /// Value of the rewards taxed as income when received, excluding refunds and returned
+    /// bonds or principal.
+    pub fn income(&self, events: &[RewardEvent]) -> f64 {
+        if self.income_recognition == IncomeRecognition::OnDisposal {
+            return 0.0;
+        }
+        let mut sum = 0.0;
+
+        for e in events {
+            match e {
+                RewardEvent::ProducedBlock { reward, .. } => sum += reward.fiat,
+                RewardEvent::Withdrawal { reward, .. } => sum += reward.fiat,
+                RewardEvent::MevReward { reward, .. } => sum += reward.fiat,
+                RewardEvent::MevRewardInternal { reward, .. } => sum += reward.fiat,
+                RewardEvent::SplitterDistribution { reward, .. } => sum += reward.fiat,
+                RewardEvent::Refund { .. } => {}
+                RewardEvent::MinipoolDistribution { reward, .. } => sum += reward.fiat,
+                RewardEvent::SmoothingPoolClaim { reward, .. } => sum += reward.fiat,
+                RewardEvent::BondReturn { .. } => {}
+                RewardEvent::LiquidStakingReward { reward, .. } => sum += reward.fiat,
+                RewardEvent::Deposit { .. } => {}
+                RewardEvent::PrincipalReturn { .. } => {}
+                RewardEvent::Consolidation { .. } => {}
+                RewardEvent::WithdrawalRequest { .. } => {}
+                RewardEvent::TokenReward { reward, .. } => sum += reward.fiat,
+                RewardEvent::Unclassified { .. } => {}
+                RewardEvent::Outgoing { .. } => {}
+            }
+        }
+        sum
+    }
+}
+
+impl Default for TaxProfile {
+    fn default() -> Self {
+        Self::builtin(Jurisdiction::default())
+    }
+}
+
+/// Month and day given as `MM-DD`, e.g. `04-06`.
+fn parse_month_day(start: &str) -> Result<(u32, u32), String> {
+    let start = start.trim();
+    let invalid = || format!("Invalid TAX_FISCAL_YEAR_START {}, expected MM-DD", start);
+    let (month, day) = start.split_once('-').ok_or_else(invalid)?;
+    let month = month.parse().map_err(|_| invalid())?;
+    let day = day.parse().map_err(|_| invalid())?;
+    // Any day of a leap year
+    NaiveDate::from_ymd_opt(2024, month, day).ok_or_else(invalid)?;
+    Ok((month, day))
+}
+
+/// Offset given as `+01:00`, `-05:00` or in whole hours like `1`.
+fn parse_offset(offset: &str) -> Result<FixedOffset, String> {
+    let offset = offset.trim();
+    let invalid = || format!("Invalid UTC offset {}", offset);
+    let (sign, rest) = match offset.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, offset.trim_start_matches('+')),
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let hours: i32 = hours.parse().map_err(|_| invalid())?;
+    let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).ok_or_else(invalid)
+}
+
+#[test]
+fn test_fiscal_years() {
+    let date = |year: i32, month: u32, day: u32, hour: u32| {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    };
+
+    let de = TaxProfile::builtin(Jurisdiction::De);
+    // New Year's Eve at 23:30 UTC is already 2024 in Germany
+    assert!(de.in_fiscal_year(&date(2023, 1, 1, 0), 2023));
+    assert!(!de.in_fiscal_year(&date(2023, 12, 31, 23), 2023));
+    assert_eq!(de.fiscal_year_name(2023), "2023");
+    assert_eq!(de.gains_exemption(2023), Exemption::Threshold(600.0));
+    assert_eq!(de.gains_exemption(2024), Exemption::Threshold(1000.0));
+    let disposal = |acquired: Option<NaiveDateTime>| Disposal {
+        date: date(2023, 9, 1, 0),
+        acquired,
+        symbol: String::from("ETH"),
+        price_id: String::from("ethereum"),
+        id: String::new(),
+        amount: 1.0,
+        proceeds: 1500.0,
+        cost: 1000.0,
+    };
+    assert_eq!(
+        de.long_term(&disposal(Some(date(2022, 1, 1, 0)))),
+        Some(true)
+    );
+    assert_eq!(
+        de.long_term(&disposal(Some(date(2023, 6, 1, 0)))),
+        Some(false)
+    );
+    assert_eq!(de.long_term(&disposal(None)), None);
+
+    let uk = TaxProfile::builtin(Jurisdiction::Uk);
+    assert_eq!(
+        uk.fiscal_year(2023),
+        (
+            NaiveDate::from_ymd_opt(2023, 4, 6).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 4, 5).unwrap()
+        )
+    );
+    assert!(uk.in_fiscal_year(&date(2024, 4, 5, 12), 2023));
+    assert_eq!(uk.fiscal_year_name(2023), "2023-24");
+    let today = |month: u32, day: u32| NaiveDate::from_ymd_opt(2024, month, day).unwrap();
+    assert_eq!(uk.last_completed_year(today(4, 5)), 2022);
+    assert_eq!(uk.last_completed_year(today(4, 6)), 2023);
+    assert_eq!(de.last_completed_year(today(1, 1)), 2023);
+    assert_eq!(Exemption::Allowance(6000.0).taxable(6500.0), 500.0);
+    assert_eq!(Exemption::Threshold(600.0).taxable(599.0), 0.0);
+
+    assert_eq!(
+        parse_offset("-05:00"),
+        Ok(FixedOffset::west_opt(5 * 3600).unwrap())
+    );
+    assert_eq!(
+        parse_offset("5:30"),
+        Ok(FixedOffset::east_opt(19800).unwrap())
+    );
+    assert_eq!("threshold:256".parse(), Ok(Exemption::Threshold(256.0)));
+
+    let leap = TaxProfile {
+        fiscal_year_start: parse_month_day("02-29").unwrap(),
+        ..de
+    };
+    assert_eq!(
+        leap.fiscal_year(2023),
+        (
+            NaiveDate::from_ymd_opt(2023, 2, 28).unwrap(),
+            NaiveDate::from_ymd_opt(2024, 2, 28).unwrap()
+        )
+    );
+    assert!(parse_month_day("02-30").is_err());
+    assert!(parse_month_day("13").is_err());
+}
@@ -1,72 +1,305 @@
-use crate::conversion::fetch_ethereum_price;
-use async_throttle::RateLimiter;
+//! Reward events and their valuation.
+
 use chrono::NaiveDateTime;
-use ethers::types::{U256, U64};
-use ethers::utils::format_ether;
-use std::sync::Arc;
+use ethers::types::{Address, U256, U64};
+use ethers::utils::format_units;
 
+/// A classified transfer to or from the reward addresses, see `process_transactions`.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum RewardEvent {
-    Withdrawal { reward: Reward },
-    ProducedBlock { reward: Reward },
-    MevReward { reward: Reward },
-    MevRewardInternal { reward: Reward },
-    Outgoing { reward: Reward, fee: U256 },
+    /// Consensus rewards swept from a validator.
+    Withdrawal {
+        /// Withdrawn amount above the principal, keyed by validator index.
+        reward: Reward,
+    },
+    /// Reward for proposing a block, paid to the fee recipient.
+    ProducedBlock {
+        /// Fees of the block paid to the fee recipient, in the native asset.
+        reward: Reward,
+    },
+    /// Transfer to the fee recipient, such as a block builder's MEV payment.
+    MevReward {
+        /// Value of the transaction, keyed by its hash.
+        reward: Reward,
+    },
+    /// Builder payment received as an internal transaction within a proposed block.
+    MevRewardInternal {
+        /// Value of the internal transaction, keyed by the hash of the transaction it's part of.
+        reward: Reward,
+    },
+    /// Payout of a fee recipient splitter contract.
+    SplitterDistribution {
+        /// Share of the execution address, keyed by the transaction hash.
+        reward: Reward,
+    },
+    /// Value returned by a contract called from one of the reward addresses.
+    Refund {
+        /// Returned value, keyed by the transaction hash.
+        reward: Reward,
+    },
+    /// Rewards of a Rocket Pool minipool distributed to the node.
+    MinipoolDistribution {
+        /// Share of the node, excluding any returned bond.
+        reward: Reward,
+    },
+    /// Claim from the Rocket Pool smoothing pool.
+    SmoothingPoolClaim {
+        /// Claimed ETH, keyed by the claim transaction hash.
+        reward: Reward,
+    },
+    /// Rocket Pool bond returned after a minipool exit.
+    BondReturn {
+        /// Returned part of the bond, which is less than the bond after penalties.
+        reward: Reward,
+    },
+    /// Daily accrual of a liquid staking token.
+    LiquidStakingReward {
+        /// Income of the day in the native asset, received at the end of it.
+        reward: Reward,
+        /// Symbol of the token.
+        symbol: String,
+    },
+    /// Stake deposited to the beacon deposit contract.
+    Deposit {
+        /// Deposited amount of the staking asset, keyed by validator index, or by pubkey
+        /// if the index couldn't be resolved.
+        reward: Reward,
+        /// Public key of the validator.
+        pubkey: String,
+    },
+    /// Principal withdrawn by a full exit or partial withdrawal request.
+    PrincipalReturn {
+        /// Principal part of the withdrawal, keyed by validator index.
+        reward: Reward,
+    },
+    /// Principal moved to the validator of `reward`, the `fee` paid in the native asset.
+    Consolidation {
+        /// Principal moved to the target validator, keyed by its index.
+        reward: Reward,
+        /// Index of the validator the principal was moved from.
+        source: String,
+        /// Fee paid to the consolidation request contract by the sender of the request.
+        fee: Reward,
+    },
+    /// EIP-7002 request, `reward` holding the request fee. A zero `amount` requests an exit.
+    WithdrawalRequest {
+        /// Fee paid to the withdrawal request contract, keyed by validator index.
+        reward: Reward,
+        /// Requested amount in wei of the staking asset, zero for an exit.
+        amount: U256,
+        /// Index of the resulting withdrawal, once processed.
+        withdrawal: Option<u64>,
+    },
+    /// ERC-20 token received as a reward.
+    TokenReward {
+        /// The received amount, in base units of the token.
+        reward: Reward,
+        /// Contract, symbol and decimals of the received token.
+        token: Token,
+    },
+    /// Value received by a contract call that couldn't be classified. Kept in the balance
+    /// and listed for review, but not counted as income.
+    Unclassified {
+        /// Received value, keyed by the transaction hash.
+        reward: Reward,
+    },
+    /// Transfer out of the reward addresses.
+    Outgoing {
+        /// Value sent, excluding staked deposits and request fees, keyed by the
+        /// transaction hash.
+        reward: Reward,
+        /// Gas paid for the transfer in wei.
+        fee: U256,
+    },
+}
+
+/// An ERC-20 token received as a reward. `Reward::amount` is denominated in the token's base units.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Token {
+    /// Address of the token contract.
+    pub contract: Address,
+    /// Symbol of the token.
+    pub symbol: String,
+    /// Decimals of the token.
+    pub decimals: u32,
+    /// CoinGecko id pricing the token.
+    pub price_id: String,
+}
+
+impl Token {
+    /// The asset amounts of the token are denominated in.
+    pub fn asset(&self) -> Asset {
+        Asset {
+            price_id: self.price_id.clone(),
+            decimals: self.decimals,
+        }
+    }
+}
+
+/// The asset a `Reward::amount` is denominated in.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Asset {
+    /// CoinGecko id pricing the asset.
+    pub price_id: String,
+    /// Decimals of the base unit, 18 for wei.
+    pub decimals: u32,
 }
 
+/// A transfer or fee in an asset, valued in the reporting currency once priced.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Reward {
+    /// Block the transfer was included in.
     pub block: U64,
+    /// Transaction hash or validator index.
     pub id: String,
+    /// When the transfer happened, in UTC.
     pub date: NaiveDateTime,
+    /// Amount in base units of `asset`.
     pub amount: U256,
+    /// The asset of `amount`.
+    pub asset: Asset,
+    /// Reward address receiving the reward, or paying the transfer or fee.
+    pub address: Address,
+    /// Price per whole unit of `asset` in the reporting currency.
     pub price: f64,
+    /// Value of `amount` in the reporting currency.
     pub fiat: f64,
 }
 impl Eq for Reward {}
 
 impl Reward {
-    pub async fn new(
+    /// An unpriced reward received at the unix `timestamp`, see `Pricer::price_events`.
+    pub fn new(
         block: U64,
         id: String,
         timestamp: String,
         amount: U256,
-        limiter: Arc<RateLimiter>,
+        asset: Asset,
+        address: Address,
     ) -> Self {
         let unix_time = timestamp.parse::<i64>().unwrap();
         let date = NaiveDateTime::from_timestamp_opt(unix_time, 0).unwrap();
-        let price = fetch_ethereum_price(&date, limiter.clone()).await.unwrap();
+        Self::priced(block, id, date, amount, asset, address, 0.0)
+    }
 
-        Reward {
+    /// A reward received at `date`, valued at `price` per whole unit of its asset.
+    pub fn priced(
+        block: U64,
+        id: String,
+        date: NaiveDateTime,
+        amount: U256,
+        asset: Asset,
+        address: Address,
+        price: f64,
+    ) -> Self {
+        let mut reward = Reward {
             block,
             id,
             date,
             amount,
-            price,
-            fiat: format_ether(amount).parse::<f64>().unwrap() * price,
+            asset,
+            address,
+            price: 0.0,
+            fiat: 0.0,
+        };
+        reward.set_price(price);
+        reward
+    }
+
+    /// Values the reward at `price` per whole unit of its asset.
+    pub fn set_price(&mut self, price: f64) {
+        self.price = price;
+        self.fiat = format_units(self.amount, self.asset.decimals)
+            .unwrap()
+            .parse::<f64>()
+            .unwrap()
+            * price;
+    }
+}
+
+/// Principal staked with and returned by a validator, valued at the time of each transfer.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Principal {
+    /// Principal deposited.
+    pub deposited: U256,
+    /// Value of the deposits in the reporting currency.
+    pub deposited_fiat: f64,
+    /// Principal returned.
+    pub returned: U256,
+    /// Value of the returns in the reporting currency.
+    pub returned_fiat: f64,
+}
+
+impl RewardEvent {
+    /// The primary reward of the event.
+    pub fn reward(&self) -> &Reward {
+        match self {
+            RewardEvent::ProducedBlock { reward } => reward,
+            RewardEvent::Withdrawal { reward } => reward,
+            RewardEvent::MevReward { reward } => reward,
+            RewardEvent::MevRewardInternal { reward } => reward,
+            RewardEvent::SplitterDistribution { reward } => reward,
+            RewardEvent::Refund { reward } => reward,
+            RewardEvent::MinipoolDistribution { reward } => reward,
+            RewardEvent::SmoothingPoolClaim { reward } => reward,
+            RewardEvent::BondReturn { reward } => reward,
+            RewardEvent::LiquidStakingReward { reward, .. } => reward,
+            RewardEvent::Deposit { reward, .. } => reward,
+            RewardEvent::PrincipalReturn { reward } => reward,
+            RewardEvent::Consolidation { reward, .. } => reward,
+            RewardEvent::WithdrawalRequest { reward, .. } => reward,
+            RewardEvent::TokenReward { reward, .. } => reward,
+            RewardEvent::Unclassified { reward } => reward,
+            RewardEvent::Outgoing { reward, fee: _gas } => reward,
+        }
+    }
+
+    /// Mutable access to the primary reward of the event.
+    pub fn reward_mut(&mut self) -> &mut Reward {
+        match self {
+            RewardEvent::ProducedBlock { reward } => reward,
+            RewardEvent::Withdrawal { reward } => reward,
+            RewardEvent::MevReward { reward } => reward,
+            RewardEvent::MevRewardInternal { reward } => reward,
+            RewardEvent::SplitterDistribution { reward } => reward,
+            RewardEvent::Refund { reward } => reward,
+            RewardEvent::MinipoolDistribution { reward } => reward,
+            RewardEvent::SmoothingPoolClaim { reward } => reward,
+            RewardEvent::BondReturn { reward } => reward,
+            RewardEvent::LiquidStakingReward { reward, .. } => reward,
+            RewardEvent::Deposit { reward, .. } => reward,
+            RewardEvent::PrincipalReturn { reward } => reward,
+            RewardEvent::Consolidation { reward, .. } => reward,
+            RewardEvent::WithdrawalRequest { reward, .. } => reward,
+            RewardEvent::TokenReward { reward, .. } => reward,
+            RewardEvent::Unclassified { reward } => reward,
+            RewardEvent::Outgoing { reward, fee: _gas } => reward,
         }
     }
 }
 
-impl Ord for RewardEvent {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        let date = match self {
-            RewardEvent::ProducedBlock { reward } => reward.date,
-            RewardEvent::Withdrawal { reward } => reward.date,
-            RewardEvent::MevReward { reward } => reward.date,
-            RewardEvent::MevRewardInternal { reward } => reward.date,
-            RewardEvent::Outgoing { reward, fee: _gas } => reward.date,
-        };
+impl RewardEvent {
+    /// The reward, and the fee of a consolidation, which is paid in another asset on
+    /// Gnosis Chain and priced on its own.
+    pub fn rewards_mut(&mut self) -> Vec<&mut Reward> {
+        match self {
+            RewardEvent::Consolidation { reward, fee, .. } => vec![reward, fee],
+            event => vec![event.reward_mut()],
+        }
+    }
 
-        let other_date = match other {
-            RewardEvent::ProducedBlock { reward } => reward.date,
-            RewardEvent::Withdrawal { reward } => reward.date,
-            RewardEvent::MevReward { reward } => reward.date,
-            RewardEvent::MevRewardInternal { reward } => reward.date,
-            RewardEvent::Outgoing { reward, fee: _gas } => reward.date,
-        };
+    /// The reward, and the fee of a consolidation.
+    pub fn rewards(&self) -> Vec<&Reward> {
+        match self {
+            RewardEvent::Consolidation { reward, fee, .. } => vec![reward, fee],
+            event => vec![event.reward()],
+        }
+    }
+}
 
-        return date.cmp(&other_date);
+impl Ord for RewardEvent {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.reward().date.cmp(&other.reward().date)
     }
 }
 
@@ -75,3 +308,52 @@ impl PartialOrd for RewardEvent {
         Some(self.cmp(other))
     }
 }
+
+#[test]
+fn test_priced_reward() {
+    let eth = Asset {
+        price_id: String::from("ethereum"),
+        decimals: 18,
+    };
+    let date = NaiveDateTime::from_timestamp_opt(1700000000, 0).unwrap();
+
+    let reward = Reward::priced(
+        U64::one(),
+        String::new(),
+        date,
+        U256::exp10(17) * 15,
+        eth.clone(),
+        Address::zero(),
+        2000.0,
+    );
+    assert_eq!(reward.fiat, 3000.0);
+
+    let mut reward = Reward::new(
+        U64::one(),
+        String::new(),
+        String::from("1700000000"),
+        U256::exp10(18),
+        eth,
+        Address::zero(),
+    );
+    assert_eq!((reward.date, reward.fiat), (date, 0.0));
+    reward.set_price(1800.0);
+    assert_eq!(reward.fiat, 1800.0);
+
+    let usdc = Token {
+        contract: Address::zero(),
+        symbol: String::from("USDC"),
+        decimals: 6,
+        price_id: String::from("usd-coin"),
+    };
+    let reward = Reward::priced(
+        U64::one(),
+        String::new(),
+        date,
+        U256::from(2_500_000),
+        usdc.asset(),
+        Address::zero(),
+        0.9,
+    );
+    assert_eq!(reward.fiat, 2.25);
+}
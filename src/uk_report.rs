@@ -0,0 +1,371 @@
+//! UK capital gains of the reward assets under the HMRC matching rules, per fiscal year
+//! of the tax profile, from 6 April to 5 April for the UK.
+//!
+//! Disposals are matched to acquisitions of the same day first, then to acquisitions in
+//! the following 30 days, and finally to the Section 104 pool at its average cost.
+
+use crate::lots::{Movement, DUST};
+use crate::reports::{Report, ReportWriter};
+use crate::tax_profile::TaxProfile;
+use chrono::{Duration, NaiveDate};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Write;
+use std::path::Path;
+
+/// HMRC rule a disposal is matched by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    SameDay,
+    BedAndBreakfast,
+    Section104,
+    /// Units beyond the known acquisitions, such as principal deposited before the tracked
+    /// history, need their cost added by hand.
+    Unmatched,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::SameDay => write!(f, "Same day"),
+            Rule::BedAndBreakfast => write!(f, "30 days"),
+            Rule::Section104 => write!(f, "Section 104"),
+            Rule::Unmatched => write!(f, "Unmatched"),
+        }
+    }
+}
+
+/// Part of the disposals of a day matched by one rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub date: NaiveDate,
+    pub symbol: String,
+    pub rule: Rule,
+    pub amount: f64,
+    pub proceeds: f64,
+    pub cost: f64,
+}
+
+impl Match {
+    pub fn gain(&self) -> f64 {
+        self.proceeds - self.cost
+    }
+}
+
+/// Units acquired and disposed of on a day, with their value.
+#[derive(Debug, Clone, Default)]
+struct Day {
+    acquired: f64,
+    cost: f64,
+    disposed: f64,
+    proceeds: f64,
+}
+
+impl Day {
+    /// Takes `amount` acquired units, returning their cost.
+    fn take_acquired(&mut self, amount: f64) -> f64 {
+        let cost = self.cost * amount / self.acquired;
+        self.cost -= cost;
+        self.acquired -= amount;
+        cost
+    }
+
+    /// Takes `amount` disposed units, returning their proceeds.
+    fn take_disposed(&mut self, amount: f64) -> f64 {
+        let proceeds = self.proceeds * amount / self.disposed;
+        self.proceeds -= proceeds;
+        self.disposed -= amount;
+        proceeds
+    }
+}
+
+/// Matches the disposals among `movements` of all years to acquisitions, by the days of
+/// the jurisdiction of `profile`. Returned principal is acquired on the day it was
+/// deposited, principal of unknown acquisition stays out of the pool. Staked rewards stay
+/// in the pool while staked.
+pub fn matches(movements: &[Movement], profile: &TaxProfile) -> Vec<Match> {
+    let mut assets: BTreeMap<&str, (&str, BTreeMap<NaiveDate, Day>)> = BTreeMap::new();
+    for movement in movements.iter().filter(|movement| !movement.staked) {
+        let (_, days) = assets
+            .entry(&movement.price_id)
+            .or_insert((&movement.symbol, BTreeMap::new()));
+        let date = match movement.amount > 0.0 {
+            true => match movement.acquired {
+                Some(acquired) => acquired,
+                None => continue,
+            },
+            false => movement.date,
+        };
+        let day = days.entry(profile.local(&date).date()).or_default();
+        let value = movement.amount.abs() * movement.price;
+        match movement.amount > 0.0 {
+            true => {
+                day.acquired += movement.amount;
+                day.cost += value;
+            }
+            false => {
+                day.disposed -= movement.amount;
+                day.proceeds += value;
+            }
+        }
+    }
+
+    let mut matches = vec![];
+    for (symbol, mut days) in assets.into_values() {
+        let mut matched = |date: NaiveDate, rule: Rule, amount: f64, proceeds: f64, cost| {
+            matches.push(Match {
+                date,
+                symbol: symbol.to_string(),
+                rule,
+                amount,
+                proceeds,
+                cost,
+            })
+        };
+        for (date, day) in days.iter_mut() {
+            let amount = day.acquired.min(day.disposed);
+            if amount > DUST {
+                let cost = day.take_acquired(amount);
+                let proceeds = day.take_disposed(amount);
+                matched(*date, Rule::SameDay, amount, proceeds, cost);
+            }
+        }
+
+        let dates: Vec<NaiveDate> = days.keys().copied().collect();
+        for date in &dates {
+            let window = (*date + Duration::days(1))..=(*date + Duration::days(30));
+            for later in dates.iter().filter(|later| window.contains(later)) {
+                let available = days[later].acquired;
+                let amount = days[date].disposed.min(available);
+                if amount <= DUST {
+                    continue;
+                }
+                let cost = days.get_mut(later).unwrap().take_acquired(amount);
+                let proceeds = days.get_mut(date).unwrap().take_disposed(amount);
+                matched(*date, Rule::BedAndBreakfast, amount, proceeds, cost);
+            }
+        }
+
+        let (mut pooled, mut pool_cost) = (0.0, 0.0);
+        for (date, day) in days.iter_mut() {
+            if day.acquired > DUST {
+                pooled += day.acquired;
+                pool_cost += day.cost;
+            }
+            let amount = day.disposed.min(pooled);
+            if amount > DUST {
+                let cost = pool_cost * amount / pooled;
+                pooled -= amount;
+                pool_cost -= cost;
+                let proceeds = day.take_disposed(amount);
+                matched(*date, Rule::Section104, amount, proceeds, cost);
+            }
+            if day.disposed > DUST {
+                let amount = day.disposed;
+                let proceeds = day.take_disposed(amount);
+                matched(*date, Rule::Unmatched, amount, proceeds, 0.0);
+            }
+        }
+    }
+
+    matches.sort_by_key(|matched| matched.date);
+    matches
+}
+
+pub struct UkReport;
+
+impl ReportWriter for UkReport {
+    fn write(&self, report: &Report, directory: &Path) -> Result<(), Box<dyn Error>> {
+        if report.currency != "GBP" {
+            return Err(format!("UK gains are reported in GBP, not {}", report.currency).into());
+        }
+        let profile = &report.profile;
+        let path = directory.join(format!(
+            "Capital Gains {}.md",
+            profile.fiscal_year_name(report.year)
+        ));
+        let matches = matches(&report.movements, profile);
+        std::fs::write(path, render(report.year, profile, &matches))?;
+        Ok(())
+    }
+}
+
+/// Summary of the tax year starting in `year`, with the matches of its disposals.
+fn render(year: i32, profile: &TaxProfile, matches: &[Match]) -> String {
+    let (first, last) = profile.fiscal_year(year);
+    let in_year: Vec<&Match> = matches
+        .iter()
+        .filter(|matched| matched.date >= first && matched.date <= last)
+        .collect();
+    let (matched, unmatched): (Vec<&Match>, Vec<&Match>) = in_year
+        .into_iter()
+        .partition(|matched| matched.rule != Rule::Unmatched);
+
+    let total = |value: fn(&Match) -> f64| matched.iter().fold(0.0, |total, m| total + value(m));
+    // Gains and losses are per disposal, i.e. all units of an asset disposed of on a day
+    let mut disposals: BTreeMap<(NaiveDate, &str), f64> = BTreeMap::new();
+    for m in &matched {
+        *disposals.entry((m.date, &m.symbol)).or_default() += m.gain();
+    }
+    let gains = disposals
+        .values()
+        .fold(0.0, |total, gain| total + gain.max(0.0));
+    let losses = disposals
+        .values()
+        .fold(0.0, |total, gain| total - gain.min(0.0));
+    let net = gains - losses;
+    let exemption = profile.gains_exemption(year);
+
+    let mut markdown = format!(
+        "# Capital Gains {}\n\nTax year {} to {}, all amounts in GBP.\n\n",
+        profile.fiscal_year_name(year),
+        first,
+        last
+    );
+    writeln!(markdown, "| | GBP |\n| --- | ---: |").unwrap();
+    for (label, value) in [
+        ("Disposal proceeds", total(|m| m.proceeds)),
+        ("Allowable costs", total(|m| m.cost)),
+        ("Gains", gains),
+        ("Losses", losses),
+        ("Net gains", net),
+        ("Annual exempt amount", exemption.amount()),
+        ("**Taxable gains**", exemption.taxable(net.max(0.0))),
+    ] {
+        writeln!(markdown, "| {} | {:.2} |", label, value).unwrap();
+    }
+
+    writeln!(markdown, "\n## Disposals\n").unwrap();
+    write_matches(&mut markdown, &matched);
+    if !unmatched.is_empty() {
+        writeln!(
+            markdown,
+            "\nDisposals beyond the known acquisitions, such as principal deposited before \
+             the tracked history, are not included and need their cost added by hand:\n"
+        )
+        .unwrap();
+        write_matches(&mut markdown, &unmatched);
+    }
+    markdown
+}
+
+fn write_matches(markdown: &mut String, matches: &[&Match]) {
+    writeln!(
+        markdown,
+        "| Date | Asset | Rule | Amount | Proceeds | Cost | Gain |"
+    )
+    .unwrap();
+    writeln!(markdown, "| --- | --- | --- | ---: | ---: | ---: | ---: |").unwrap();
+    for matched in matches {
+        writeln!(
+            markdown,
+            "| {} | {} | {} | {:.8} | {:.2} | {:.2} | {:.2} |",
+            matched.date,
+            matched.symbol,
+            matched.rule,
+            matched.amount,
+            matched.proceeds,
+            matched.cost,
+            matched.gain()
+        )
+        .unwrap();
+    }
+}
+
+#[test]
+fn test_matching_rules() {
+    let movement = |month: u32, day: u32, amount: f64, price: f64| {
+        let date = NaiveDate::from_ymd_opt(2023, month, day)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap();
+        Movement {
+            date,
+            symbol: String::from("ETH"),
+            price_id: String::from("ethereum"),
+            id: String::new(),
+            amount,
+            price,
+            acquired: Some(date),
+            income: amount > 0.0,
+            staked: false,
+        }
+    };
+    let movements = vec![
+        movement(1, 1, 2.0, 1000.0),
+        movement(3, 1, 1.0, 1600.0),
+        movement(6, 1, 0.5, 1200.0),
+        movement(6, 1, -1.0, 1500.0),
+        movement(6, 15, 0.2, 1300.0),
+        movement(7, 1, -3.0, 1000.0),
+    ];
+
+    let profile = TaxProfile::builtin(crate::tax_profile::Jurisdiction::Uk);
+    let summary: Vec<(Rule, f64, f64)> = matches(&movements, &profile)
+        .iter()
+        .map(|matched| {
+            let round = |value: f64| (value * 1e6).round() / 1e6;
+            (matched.rule, round(matched.amount), round(matched.gain()))
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (Rule::SameDay, 0.5, 150.0),
+            (Rule::BedAndBreakfast, 0.2, 40.0),
+            // Pool of 3 ETH at an average cost of 1200
+            (Rule::Section104, 0.3, 90.0),
+            (Rule::Section104, 2.7, -540.0),
+            (Rule::Unmatched, 0.3, 300.0),
+        ]
+    );
+
+    let markdown = render(2023, &profile, &matches(&movements, &profile));
+    assert!(markdown.contains("| Net gains | -260.00 |"));
+    assert!(markdown.contains("| **Taxable gains** | 0.00 |"));
+    assert!(
+        markdown.contains("| 2023-07-01 | ETH | Unmatched | 0.30000000 | 300.00 | 0.00 | 300.00 |")
+    );
+}
+
+#[test]
+fn test_returned_principal_is_pooled() {
+    let date = |year: i32, month: u32| {
+        NaiveDate::from_ymd_opt(year, month, 1)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    };
+    let movement = |amount: f64, price: f64, acquired: Option<chrono::NaiveDateTime>| Movement {
+        date: date(2023, 7),
+        symbol: String::from("ETH"),
+        price_id: String::from("ethereum"),
+        id: String::from("1"),
+        amount,
+        price,
+        acquired,
+        income: false,
+        staked: false,
+    };
+    let movements = vec![
+        // Principal deposited in 2021 at 1000, and a bond of unknown acquisition
+        movement(32.0, 1000.0, Some(date(2021, 1))),
+        movement(8.0, 0.0, None),
+        movement(-40.0, 1500.0, Some(date(2023, 7))),
+    ];
+
+    let profile = TaxProfile::builtin(crate::tax_profile::Jurisdiction::Uk);
+    let summary: Vec<(Rule, f64, f64)> = matches(&movements, &profile)
+        .iter()
+        .map(|matched| (matched.rule, matched.amount, matched.gain()))
+        .collect();
+    // Not matched to the return on the same day
+    assert_eq!(
+        summary,
+        vec![
+            (Rule::Section104, 32.0, 16000.0),
+            (Rule::Unmatched, 8.0, 12000.0),
+        ]
+    );
+}
@@ -0,0 +1,147 @@
+//! US figures: rewards as ordinary income for Schedule 1 and disposals for Form 8949.
+
+use crate::lots::Disposal;
+use crate::reports::{Category, Report, ReportWriter};
+use crate::tax_profile::TaxProfile;
+use std::error::Error;
+use std::path::Path;
+
+const FORM_8949_COLUMNS: [&str; 10] = [
+    "Part",
+    "(a) Description of property",
+    "(b) Date acquired",
+    "(c) Date sold or disposed of",
+    "(d) Proceeds",
+    "(e) Cost or other basis",
+    "(f) Code",
+    "(g) Amount of adjustment",
+    "(h) Gain or (loss)",
+    "Transaction",
+];
+
+pub struct UsReport;
+
+impl ReportWriter for UsReport {
+    fn write(&self, report: &Report, directory: &Path) -> Result<(), Box<dyn Error>> {
+        if report.currency != "USD" {
+            return Err(format!("US forms are filed in USD, not {}", report.currency).into());
+        }
+
+        let path = directory.join(format!("Form 8949 {}.csv", report.year));
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(FORM_8949_COLUMNS)?;
+        for disposal in &report.disposals {
+            writer.write_record(form_8949_row(disposal, &report.profile))?;
+        }
+        writer.flush()?;
+
+        let path = directory.join(format!("Schedule 1 {}.csv", report.year));
+        let mut writer = csv::Writer::from_path(path)?;
+        writer.write_record(["Income", "USD"])?;
+        for (income, value) in ordinary_income(report) {
+            writer.write_record([income, format!("{:.2}", value)])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Rewards at their fair market value when received, by kind and in total, reported as
+/// digital assets received as ordinary income on Schedule 1. Fees aren't deducted.
+fn ordinary_income(report: &Report) -> Vec<(String, f64)> {
+    let income = |category: Category| {
+        report
+            .entries
+            .iter()
+            .filter(|entry| entry.category == category)
+            .fold(0.0, |total, entry| total + entry.fiat)
+    };
+    let rows = [
+        ("Execution rewards", income(Category::Execution)),
+        ("Consensus rewards", income(Category::Consensus)),
+        ("Token rewards", income(Category::Tokens)),
+    ];
+    let total = rows.iter().fold(0.0, |total, (_, value)| total + value);
+    rows.into_iter()
+        .chain([("Total ordinary income", total)])
+        .map(|(income, value)| (income.to_string(), value))
+        .collect()
+}
+
+/// Part I lists short-term, Part II long-term disposals. Disposals of units not acquired
+/// as rewards need their basis and holding period added by hand.
+fn form_8949_row(disposal: &Disposal, profile: &TaxProfile) -> [String; 10] {
+    let part = match profile.long_term(disposal) {
+        Some(false) => "I",
+        Some(true) => "II",
+        None => "Unknown basis",
+    };
+    [
+        part.to_string(),
+        format!("{:.8} {}", disposal.amount, disposal.symbol),
+        disposal
+            .acquired
+            .map(|acquired| acquired.format("%m/%d/%Y").to_string())
+            .unwrap_or_default(),
+        disposal.date.format("%m/%d/%Y").to_string(),
+        format!("{:.2}", disposal.proceeds),
+        format!("{:.2}", disposal.cost),
+        String::new(),
+        String::new(),
+        format!("{:.2}", disposal.gain()),
+        disposal.id.clone(),
+    ]
+}
+
+#[test]
+fn test_form_8949_row() {
+    use crate::tax_profile::Jurisdiction;
+    use chrono::NaiveDate;
+
+    let date = |year: i32, month: u32| {
+        NaiveDate::from_ymd_opt(year, month, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    };
+    let disposal = Disposal {
+        date: date(2023, 9),
+        acquired: Some(date(2022, 1)),
+        symbol: String::from("ETH"),
+        price_id: String::from("ethereum"),
+        id: String::from("0x01"),
+        amount: 0.5,
+        proceeds: 825.0,
+        cost: 1000.0,
+    };
+    let profile = TaxProfile::builtin(Jurisdiction::Us);
+
+    assert_eq!(
+        form_8949_row(&disposal, &profile),
+        [
+            "II",
+            "0.50000000 ETH",
+            "01/01/2022",
+            "09/01/2023",
+            "825.00",
+            "1000.00",
+            "",
+            "",
+            "-175.00",
+            "0x01"
+        ]
+    );
+    let short = Disposal {
+        acquired: Some(date(2023, 1)),
+        ..disposal.clone()
+    };
+    assert_eq!(form_8949_row(&short, &profile)[0], "I");
+    let unknown = Disposal {
+        acquired: None,
+        ..disposal
+    };
+    assert_eq!(
+        form_8949_row(&unknown, &profile)[..3],
+        ["Unknown basis", "0.50000000 ETH", ""]
+    );
+}
@@ -0,0 +1,232 @@
+//! Report as an Excel workbook with a sheet per section and a summary, totals as formulas.
+
+use crate::reports::{Cell, Report, ReportWriter, Role, Section};
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook, Worksheet, XlsxError};
+use std::error::Error;
+use std::path::Path;
+
+const DATE_FORMAT: &str = "yyyy-mm-dd hh:mm:ss";
+const AMOUNT_FORMAT: &str = "0.00000000";
+const FIAT_FORMAT: &str = "#,##0.00";
+
+pub struct XlsxReport;
+
+impl ReportWriter for XlsxReport {
+    fn write(&self, report: &Report, directory: &Path) -> Result<(), Box<dyn Error>> {
+        let path = directory.join(format!("Staking Rewards {}.xlsx", report.year));
+        write_workbook(report, &path)?;
+        Ok(())
+    }
+}
+
+struct Formats {
+    header: Format,
+    date: Format,
+    integer: Format,
+    amount: Format,
+    fiat: Format,
+    total: Format,
+    total_amount: Format,
+    total_fiat: Format,
+}
+
+impl Formats {
+    fn new() -> Self {
+        Formats {
+            header: Format::new().set_bold(),
+            date: Format::new().set_num_format(DATE_FORMAT),
+            integer: Format::new().set_num_format("0"),
+            amount: Format::new().set_num_format(AMOUNT_FORMAT),
+            fiat: Format::new().set_num_format(FIAT_FORMAT),
+            total: Format::new().set_bold(),
+            total_amount: Format::new().set_bold().set_num_format(AMOUNT_FORMAT),
+            total_fiat: Format::new().set_bold().set_num_format(FIAT_FORMAT),
+        }
+    }
+}
+
+fn write_workbook(report: &Report, path: &Path) -> Result<(), XlsxError> {
+    let formats = Formats::new();
+    let mut workbook = Workbook::new();
+
+    for section in &report.sections {
+        let worksheet = workbook.add_worksheet().set_name(&section.title)?;
+        write_header(worksheet, &formats, &section.columns)?;
+        for (index, row) in section.rows.iter().enumerate() {
+            for (column, cell) in row.iter().enumerate() {
+                write_cell(worksheet, &formats, index as u32 + 1, column as u16, cell)?;
+            }
+        }
+        if !section.totals.is_empty() {
+            let total = section.rows.len() as u32 + 1;
+            worksheet.write_string_with_format(total, 0, "Total", &formats.total)?;
+            for column in &section.totals {
+                let format = match section.amount_total() == Some(*column) {
+                    true => &formats.total_amount,
+                    false => &formats.total_fiat,
+                };
+                worksheet.write_formula_with_format(
+                    total,
+                    *column as u16,
+                    format!(
+                        "=SUM({}:{})",
+                        cell_name(1, *column),
+                        cell_name(total - 1, *column)
+                    )
+                    .as_str(),
+                    format,
+                )?;
+            }
+        }
+        worksheet.autofit();
+    }
+
+    let summary = workbook.add_worksheet().set_name("Summary")?;
+    write_header(
+        summary,
+        &formats,
+        &["Report", "Amount", "Symbol", &report.currency].map(String::from),
+    )?;
+    let counted: Vec<&Section> = report
+        .sections
+        .iter()
+        .filter(|section| section.role != Role::Other && section.fiat_total().is_some())
+        .collect();
+    let mut net = String::from("=0");
+    for (index, section) in counted.iter().enumerate() {
+        let row = index as u32 + 1;
+        // Totals are in the row below the last one of the section
+        let total = section.rows.len() as u32 + 1;
+        summary.write_string(row, 0, &section.title)?;
+        if let Some(amount) = section.amount_total() {
+            summary.write_formula_with_format(
+                row,
+                1,
+                format!("='{}'!{}", section.title, cell_name(total, amount)).as_str(),
+                &formats.amount,
+            )?;
+            summary.write_string(row, 2, &section.columns[amount])?;
+        }
+        let fiat = section.fiat_total().unwrap();
+        summary.write_formula_with_format(
+            row,
+            3,
+            format!("='{}'!{}", section.title, cell_name(total, fiat)).as_str(),
+            &formats.fiat,
+        )?;
+        let sign = match section.role {
+            Role::Expense => '-',
+            _ => '+',
+        };
+        net.push_str(&format!("{}{}", sign, cell_name(row, 3)));
+    }
+    // Only the values in the reporting currency add up, as rewards are paid in several assets
+    let row = counted.len() as u32 + 1;
+    summary.write_string_with_format(row, 0, "Net", &formats.total)?;
+    summary.write_formula_with_format(row, 3, net.as_str(), &formats.total_fiat)?;
+    summary.autofit();
+
+    workbook.save(path)
+}
+
+/// A1 reference of the zero-based `row` and `column`.
+fn cell_name(row: u32, column: usize) -> String {
+    format!("{}{}", (b'A' + column as u8) as char, row + 1)
+}
+
+fn write_header(
+    worksheet: &mut Worksheet,
+    formats: &Formats,
+    columns: &[String],
+) -> Result<(), XlsxError> {
+    for (column, title) in columns.iter().enumerate() {
+        worksheet.write_string_with_format(0, column as u16, title, &formats.header)?;
+    }
+    worksheet.set_freeze_panes(1, 0)?;
+    Ok(())
+}
+
+fn write_cell(
+    worksheet: &mut Worksheet,
+    formats: &Formats,
+    row: u32,
+    column: u16,
+    cell: &Cell,
+) -> Result<(), XlsxError> {
+    match cell {
+        Cell::Date(date) => {
+            let date = ExcelDateTime::from_timestamp(date.timestamp())?;
+            worksheet.write_datetime_with_format(row, column, &date, &formats.date)?
+        }
+        Cell::Integer(value) => {
+            worksheet.write_number_with_format(row, column, *value as f64, &formats.integer)?
+        }
+        Cell::Text(text) => worksheet.write_string(row, column, text)?,
+        Cell::Amount(amount) => {
+            worksheet.write_number_with_format(row, column, *amount, &formats.amount)?
+        }
+        Cell::Fiat(value) => {
+            worksheet.write_number_with_format(row, column, *value, &formats.fiat)?
+        }
+    };
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_write_workbook() {
+    crate::fixtures::setup();
+
+    let config = crate::addresses::Config::from_env().unwrap();
+    let mut events = crate::processing::process_transactions(&config)
+        .await
+        .unwrap();
+    let pricer = crate::pricing::Pricer::default();
+    pricer.price_events(&mut events).await.unwrap();
+    events.sort();
+
+    let directory =
+        std::env::temp_dir().join(format!("staking-tax-test-workbook-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    XlsxReport
+        .write(
+            &Report::new(
+                &events,
+                2023,
+                &config,
+                &pricer,
+                &crate::tax_profile::TaxProfile::default(),
+            ),
+            &directory,
+        )
+        .unwrap();
+
+    let file = std::fs::File::open(directory.join("Staking Rewards 2023.xlsx")).unwrap();
+    let mut workbook = zip::ZipArchive::new(file).unwrap();
+    let mut part = |name: &str| {
+        let mut xml = String::new();
+        std::io::Read::read_to_string(&mut workbook.by_name(name).unwrap(), &mut xml).unwrap();
+        xml
+    };
+
+    let sheets = part("xl/workbook.xml");
+    assert!(sheets.contains(r#"<sheet name="Execution Rewards 2023" sheetId="1""#));
+    assert!(sheets.contains(r#"<sheet name="Summary" sheetId="9""#));
+
+    // Dates, block numbers, amounts and values are written as numbers, not text
+    let execution = part("xl/worksheets/sheet1.xml");
+    assert!(execution.contains(r#"<c r="A2" s="2"><v>45170</v></c>"#));
+    assert!(execution.contains(r#"<c r="B2" s="3"><v>18000000</v></c>"#));
+    assert!(execution.contains(r#"<c r="E2" s="4"><v>0.05</v></c>"#));
+    assert!(execution.contains(r#"<c r="G2" s="5"><v>76.006</v></c>"#));
+    assert!(execution.contains("<f>SUM(E2:E4)</f>"));
+    assert!(execution.contains("<f>SUM(G2:G4)</f>"));
+
+    let summary = part("xl/worksheets/sheet9.xml");
+    assert!(summary.contains("<f>'Execution Rewards 2023'!G5</f>"));
+}
+
+#[test]
+fn test_cell_name() {
+    assert_eq!(cell_name(0, 0), "A1");
+    assert_eq!(cell_name(4, 6), "G5");
+}